@@ -0,0 +1,63 @@
+//! Compares the cost of keeping a histogram of each station in `vhist` to `v3`, which it is based on.
+//! Run with `cargo bench --bench histogram`. The number of rows can be set with `BRC_BENCH_ROWS`.
+
+use std::time::{Duration, Instant};
+
+use brc::{ParseMode, SummarizeBytesFn};
+
+const DEFAULT_ROWS: usize = 10_000_000;
+const NUM_STATIONS: usize = 413;
+const REPEATS: usize = 5;
+
+/// Generate rows of random stations and values using a fixed seed, so every run measures the same data.
+fn generate(rows: usize) -> Vec<u8> {
+    let mut state: u64 = 0x853c_49e6_748f_ea9b;
+    let mut next = move || {
+        // xorshift64*
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    };
+    let mut data = Vec::with_capacity(rows * 16);
+    for _ in 0..rows {
+        let station = next() as usize % NUM_STATIONS;
+        let value = (next() % 1999) as i32 - 999;
+        let sign = if value < 0 { "-" } else { "" };
+        let value = value.abs();
+        data.extend(format!("station{station};{sign}{}.{}\n", value / 10, value % 10).bytes());
+    }
+    data
+}
+
+/// The fastest of a number of runs.
+fn time(summarize: SummarizeBytesFn, data: &[u8], num_slices: usize) -> Duration {
+    (0..REPEATS)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(summarize(data, None, num_slices, ParseMode::Strict).unwrap());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let rows = std::env::var("BRC_BENCH_ROWS").map_or(DEFAULT_ROWS, |rows| {
+        rows.parse().expect("BRC_BENCH_ROWS should be a number.")
+    });
+    let data = generate(rows);
+    println!("{rows} rows, {NUM_STATIONS} stations, best of {REPEATS} runs.");
+    let mut thread_counts = vec![1, num_cpus::get()];
+    thread_counts.dedup();
+    for num_slices in thread_counts {
+        let v3 = time(brc::v3::summarize_bytes, &data, num_slices);
+        let vhist = time(brc::vhist::summarize_bytes, &data, num_slices);
+        println!(
+            "{num_slices:>3} threads: v3 {:.3}s, vhist {:.3}s, {:+.1}%",
+            v3.as_secs_f64(),
+            vhist.as_secs_f64(),
+            (vhist.as_secs_f64() / v3.as_secs_f64() - 1.) * 100.
+        );
+    }
+}
@@ -0,0 +1,62 @@
+#![no_main]
+
+use brc::{ParseError, ParseMode, VERSIONS};
+use brc_fuzz::stats_without_squares;
+use libfuzzer_sys::fuzz_target;
+
+// Any input should give a summary or a `ParseError` without panicking.
+// Lenient mode should only differ from strict mode by skipping the invalid lines,
+// and every version accepting only one decimal should skip the same lines as `v0` and keep the same statistics,
+// apart from the sums of squares `v0` does not keep.
+fuzz_target!(|data: &[u8]| {
+    let v0 = brc::version("v0").unwrap();
+    let expected = (v0.summarize_bytes)(data, None, 1, ParseMode::Lenient)
+        .unwrap_or_else(|error| panic!("Version v0 failed leniently: {error}"));
+    for version in VERSIONS {
+        for num_slices in [1, 2, 3, 7] {
+            let strict = (version.summarize_bytes)(data, None, num_slices, ParseMode::Strict);
+            let lenient = (version.summarize_bytes)(data, None, num_slices, ParseMode::Lenient)
+                .unwrap_or_else(|error| {
+                    panic!(
+                        "Version {} with {num_slices} slices failed leniently: {error}",
+                        version.name
+                    )
+                });
+            if !version.capabilities.any_decimals {
+                assert_eq!(
+                    (stats_without_squares(&expected), expected.skipped_lines()),
+                    (stats_without_squares(&lenient), lenient.skipped_lines()),
+                    "Version {} with {num_slices} slices differs from v0 in lenient mode.",
+                    version.name
+                );
+            }
+            match strict {
+                Ok(summary) => {
+                    assert_eq!(
+                        summary, lenient,
+                        "Version {} with {num_slices} slices.",
+                        version.name
+                    );
+                }
+                Err(error) => {
+                    let error = error.downcast_ref::<ParseError>().unwrap_or_else(|| {
+                        panic!(
+                            "Version {} gave an error other than a `ParseError`: {error}",
+                            version.name
+                        )
+                    });
+                    assert!(
+                        error.offset() <= data.len(),
+                        "Version {}: {error}",
+                        version.name
+                    );
+                    assert!(
+                        lenient.skipped_lines().total() > 0,
+                        "Version {}: {error}",
+                        version.name
+                    );
+                }
+            }
+        }
+    }
+});
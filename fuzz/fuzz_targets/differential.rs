@@ -0,0 +1,30 @@
+#![no_main]
+
+use brc::{ParseMode, VERSIONS};
+use brc_fuzz::{measurements, reference, stats};
+use libfuzzer_sys::{arbitrary::Unstructured, fuzz_target};
+
+// Every version should agree with the reference on valid measurements, no matter how they are split into slices.
+fuzz_target!(|input: &[u8]| {
+    let Ok(data) = measurements(&mut Unstructured::new(input)) else {
+        return;
+    };
+    for version in VERSIONS {
+        let expected = reference(&data, version.capabilities.squares);
+        for num_slices in 1..=17 {
+            for mode in [ParseMode::Strict, ParseMode::Lenient] {
+                let summary = (version.summarize_bytes)(&data, None, num_slices, mode)
+                    .unwrap_or_else(|error| {
+                        panic!("Version {} with {num_slices} slices: {error}", version.name)
+                    });
+                assert_eq!(
+                    expected,
+                    stats(&summary),
+                    "Version {} with {num_slices} slices in {mode:?} mode.",
+                    version.name
+                );
+                assert_eq!(summary.skipped_lines().total(), 0);
+            }
+        }
+    }
+});
@@ -0,0 +1,117 @@
+//! Helpers shared by the fuzz targets.
+
+use std::{collections::BTreeMap, io::Write};
+
+use libfuzzer_sys::arbitrary::{Result, Unstructured};
+
+/// The statistics of a station as `(name, min, max, sum, sum of squares, count)` in tenths of a degree.
+/// The sum of squares is only there for versions with the `squares` capability.
+pub type Stats = (String, i32, i32, i64, Option<u64>, u64);
+
+/// The pairs of names sharing a hash from the tests, which are picked often so collisions are always exercised.
+const HASH_COLLISIONS: &str =
+    include_str!("../../tests/test_files/measurements-hash-collisions.txt");
+
+/// Names that are prefixes of each other, longer than the 16 bytes read at once when searching for the semicolon,
+/// or made of multi-byte characters.
+const NAMES: &[&str] = &[
+    "Ham",
+    "Hamburg",
+    "Hamburg Altona-Nord Ost",
+    "a",
+    "Ségou",
+    "İstanbul",
+    "東京",
+    "😀😀😀😀😀",
+    "St. John's",
+];
+
+/// Turn the fuzzer input into valid measurements, each a line with a name and a value with one decimal.
+/// The last line may or may not end with a newline.
+pub fn measurements(u: &mut Unstructured) -> Result<Vec<u8>> {
+    let collisions: Vec<&str> = HASH_COLLISIONS
+        .lines()
+        .filter_map(|line| Some(line.split_once(';')?.0))
+        .collect();
+    let mut data = vec![];
+    while !u.is_empty() {
+        let name = match u.int_in_range(0..=3)? {
+            0 => NAMES[u.choose_index(NAMES.len())?].to_string(),
+            1 => collisions[u.choose_index(collisions.len())?].to_string(),
+            _ => station_name(u)?,
+        };
+        let value: i32 = u.int_in_range(-999..=999)?;
+        let sign = if value < 0 { "-" } else { "" };
+        let value = value.abs();
+        writeln!(data, "{name};{sign}{}.{}", value / 10, value % 10).unwrap();
+    }
+    if u.arbitrary()? {
+        data.pop();
+    }
+    Ok(data)
+}
+
+/// An arbitrary station name, which is any non-empty string of at most 100 bytes without semicolons or newlines.
+fn station_name(u: &mut Unstructured) -> Result<String> {
+    let name: String = u.arbitrary()?;
+    let mut name: String = name.chars().filter(|&c| c != ';' && c != '\n').collect();
+    while name.len() > 100 {
+        name.pop();
+    }
+    if name.is_empty() {
+        name.push('x');
+    }
+    Ok(name)
+}
+
+/// Summarize valid measurements in the most straightforward way, sorted by station name.
+/// The sums of squares are left out unless `squares` is true, like in versions without the `squares` capability.
+pub fn reference(data: &[u8], squares: bool) -> Vec<Stats> {
+    let data = std::str::from_utf8(data).expect("Valid measurements are UTF-8.");
+    let mut stations = BTreeMap::<&str, (i32, i32, i64, u64, u64)>::new();
+    for line in data.lines() {
+        let (name, value) = line.split_once(';').expect("Every line has a semicolon.");
+        let tenths: i32 = value.replace('.', "").parse().expect("Values are numbers.");
+        let (min, max, sum, sum_squares, count) =
+            stations
+                .entry(name)
+                .or_insert((i32::MAX, i32::MIN, 0, 0, 0));
+        *min = (*min).min(tenths);
+        *max = (*max).max(tenths);
+        *sum += tenths as i64;
+        *sum_squares += (tenths as i64 * tenths as i64) as u64;
+        *count += 1;
+    }
+    stations
+        .into_iter()
+        .map(|(name, (min, max, sum, sum_squares, count))| {
+            let sum_squares = squares.then_some(sum_squares);
+            (name.to_string(), min, max, sum, sum_squares, count)
+        })
+        .collect()
+}
+
+/// The statistics of each station in the summary without the sums of squares,
+/// to compare versions with and without the `squares` capability.
+pub fn stats_without_squares(summary: &brc::Summary) -> Vec<Stats> {
+    let mut stats = stats(summary);
+    stats.iter_mut().for_each(|station| station.4 = None);
+    stats
+}
+
+/// The statistics of each station in the summary, to compare with `reference`.
+pub fn stats(summary: &brc::Summary) -> Vec<Stats> {
+    summary
+        .iter()
+        .map(|station| {
+            (
+                station.name().to_string(),
+                station.min_tenths(),
+                station.max_tenths(),
+                station.sum_tenths(),
+                station.sum_squares_tenths(),
+                station.count(),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,318 @@
+use crate::{
+    histogram::{Histogram, BUCKETS, MIN_TENTHS},
+    SkippedLines, StationStats,
+};
+
+/// Statistics computed from the values of a single station by the generic parser `v3::summarize_slice`,
+/// which `vgeneric` exposes.
+///
+/// The parser clones a given aggregator for every new station it finds in a slice,
+/// updates it with each value of that station and merges the aggregators of different slices.
+/// Finally `finish` turns each aggregator into the output for its station.
+/// All values are in tenths of a degree.
+pub trait Aggregator: Clone {
+    /// The statistics given for each station.
+    type Output;
+
+    /// Adds a value to the statistics.
+    fn update(&mut self, value: i32);
+
+    /// Combines the statistics of two disjoint sets of values for the same station.
+    /// The values of `other` come after the values of `self` in the input.
+    fn merge(&mut self, other: &Self);
+
+    /// The statistics of all the values seen.
+    fn finish(self) -> Self::Output;
+}
+
+/// Two aggregators computed in the same pass, giving both outputs.
+impl<A: Aggregator, B: Aggregator> Aggregator for (A, B) {
+    type Output = (A::Output, B::Output);
+
+    #[inline(always)]
+    fn update(&mut self, value: i32) {
+        self.0.update(value);
+        self.1.update(value);
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.0.merge(&other.0);
+        self.1.merge(&other.1);
+    }
+
+    fn finish(self) -> Self::Output {
+        (self.0.finish(), self.1.finish())
+    }
+}
+
+/// The output of an aggregator for each station, sorted by station name,
+/// along with the number of invalid lines skipped if the input was parsed in lenient mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregated<T> {
+    stations: Vec<(String, T)>,
+    skipped_lines: SkippedLines,
+}
+
+impl<T> Aggregated<T> {
+    /// Creates the result from the output for each station, which should be sorted by station name.
+    pub(crate) fn new(stations: Vec<(String, T)>, skipped_lines: SkippedLines) -> Self {
+        debug_assert!(stations.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        Self {
+            stations,
+            skipped_lines,
+        }
+    }
+
+    /// The output for each station sorted by station name.
+    pub fn stations(&self) -> &[(String, T)] {
+        &self.stations
+    }
+
+    /// The number of invalid lines skipped for each reason.
+    pub fn skipped_lines(&self) -> &SkippedLines {
+        &self.skipped_lines
+    }
+
+    /// Find the output for the station with the given name.
+    pub fn get(&self, name: &str) -> Option<&T> {
+        self.stations
+            .binary_search_by(|(station, _)| station.as_str().cmp(name))
+            .ok()
+            .map(|index| &self.stations[index].1)
+    }
+
+    /// Turns the output for each station into `StationStats`, giving a `Summary`.
+    pub fn into_summary(self, into_stats: impl Fn(String, T) -> StationStats) -> crate::Summary {
+        crate::Summary::from_stations(
+            self.stations
+                .into_iter()
+                .map(|(name, output)| into_stats(name, output)),
+        )
+        .with_skipped_lines(self.skipped_lines)
+    }
+}
+
+/// The statistics of a `StationStats`: minimum, maximum, sum and count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    min: i32,
+    max: i32,
+    total: i64,
+    count: u64,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            min: i32::MAX,
+            max: i32::MIN,
+            total: 0,
+            count: 0,
+        }
+    }
+}
+
+impl Stats {
+    /// The statistics for the station with the given name.
+    pub fn into_station_stats(self, name: impl Into<String>) -> StationStats {
+        StationStats::new(name, self.min, self.max, self.total, self.count)
+    }
+}
+
+impl Aggregator for Stats {
+    type Output = Self;
+
+    #[inline(always)]
+    fn update(&mut self, value: i32) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.total += value as i64;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.total += other.total;
+        self.count += other.count;
+    }
+
+    fn finish(self) -> Self::Output {
+        self
+    }
+}
+
+/// The sum of the squares of the values, which `StationStats::with_sum_squares` takes for the variance and standard deviation.
+/// It is kept apart from `Stats`, since it slows down the parser noticeably.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Squares {
+    total: u64,
+}
+
+impl Aggregator for Squares {
+    type Output = u64;
+
+    #[inline(always)]
+    fn update(&mut self, value: i32) {
+        self.total += (value * value) as u64;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.total += other.total;
+    }
+
+    fn finish(self) -> Self::Output {
+        self.total
+    }
+}
+
+/// The number of measurements of each possible value, giving a `Histogram`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Buckets {
+    counts: Box<[u32; BUCKETS]>,
+}
+
+impl Default for Buckets {
+    fn default() -> Self {
+        Self {
+            counts: Box::new([0; BUCKETS]),
+        }
+    }
+}
+
+impl Aggregator for Buckets {
+    type Output = Histogram;
+
+    #[inline(always)]
+    fn update(&mut self, value: i32) {
+        self.counts[(value - MIN_TENTHS) as usize] += 1;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        Histogram::from_buckets(&self.counts)
+    }
+}
+
+/// The number of values strictly above a threshold in tenths of a degree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountAbove {
+    threshold: i32,
+    count: u64,
+}
+
+impl CountAbove {
+    pub fn new(threshold: i32) -> Self {
+        Self {
+            threshold,
+            count: 0,
+        }
+    }
+}
+
+impl Aggregator for CountAbove {
+    type Output = u64;
+
+    #[inline(always)]
+    fn update(&mut self, value: i32) {
+        self.count += u64::from(value > self.threshold);
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+    }
+
+    fn finish(self) -> Self::Output {
+        self.count
+    }
+}
+
+/// The first and last value in the order of the input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FirstLast {
+    values: Option<(i32, i32)>,
+}
+
+impl Aggregator for FirstLast {
+    type Output = (i32, i32);
+
+    #[inline(always)]
+    fn update(&mut self, value: i32) {
+        let first = self.values.map_or(value, |(first, _)| first);
+        self.values = Some((first, value));
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.values = match (self.values, other.values) {
+            (Some((first, _)), Some((_, last))) => Some((first, last)),
+            (values, None) | (None, values) => values,
+        };
+    }
+
+    fn finish(self) -> Self::Output {
+        self.values
+            .expect("A station should have at least one measurement.")
+    }
+}
+
+/// The most common value, picking the smallest one on a tie.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mode {
+    buckets: Buckets,
+}
+
+impl Aggregator for Mode {
+    type Output = i32;
+
+    #[inline(always)]
+    fn update(&mut self, value: i32) {
+        self.buckets.update(value);
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.buckets.merge(&other.buckets);
+    }
+
+    fn finish(self) -> Self::Output {
+        let (index, _) = self
+            .buckets
+            .counts
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|&(_, count)| count)
+            .expect("There is a count for every possible value.");
+        index as i32 + MIN_TENTHS
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn aggregate<A: Aggregator>(mut aggregator: A, values: &[i32]) -> A {
+        values.iter().for_each(|&value| aggregator.update(value));
+        aggregator
+    }
+
+    #[test]
+    fn merge() {
+        let values = [-15, 120, 5, 5, -15, 999, -999, 120];
+        let (a, b) = values.split_at(3);
+        let prototype = ((CountAbove::new(0), FirstLast::default()), Mode::default());
+        let mut merged = aggregate(prototype.clone(), a);
+        merged.merge(&aggregate(prototype.clone(), b));
+        assert_eq!(merged.clone().finish(), ((5, (-15, 120)), -15));
+        assert_eq!(merged.finish(), aggregate(prototype, &values).finish());
+
+        let stats = aggregate(Stats::default(), &values).into_station_stats("a");
+        assert_eq!(stats.min_tenths(), -999);
+        assert_eq!(stats.max_tenths(), 999);
+        assert_eq!(stats.count(), 8);
+    }
+}
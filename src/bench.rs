@@ -0,0 +1,519 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{common, ParseMode};
+
+/// The 97.5% quantiles of Student's t-distribution for 1 to 30 degrees of freedom,
+/// giving two-sided 95% confidence intervals.
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+/// The 97.5% quantile of the normal distribution, which the t-distribution approaches.
+const Z_95: f64 = 1.960;
+
+/// The 97.5% quantile of Student's t-distribution with the given degrees of freedom, which must be positive.
+fn t_95(degrees_of_freedom: usize) -> f64 {
+    T_95.get(degrees_of_freedom - 1).copied().unwrap_or(Z_95)
+}
+
+/// The number of timings outside the Tukey fences,
+/// which are 1.5 (mild) and 3 (severe) interquartile ranges beyond the quartiles.
+/// A severe outlier is not counted as mild.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Outliers {
+    pub mild: usize,
+    pub severe: usize,
+}
+
+/// Statistics of the timings of a version in seconds, like those criterion and hyperfine report.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistics {
+    pub samples: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    /// The sample standard deviation, which is zero for a single timing.
+    pub std_dev: f64,
+    /// Half the width of the 95% confidence interval of the mean, which is infinite for a single timing.
+    pub confidence: f64,
+    pub outliers: Outliers,
+}
+
+impl Statistics {
+    /// Computes the statistics of the given timings.
+    ///
+    /// Panics if there are no timings.
+    pub fn new(timings: &[Duration]) -> Self {
+        assert!(!timings.is_empty(), "There should be at least one timing.");
+        let mut seconds: Vec<f64> = timings.iter().map(Duration::as_secs_f64).collect();
+        seconds.sort_by(f64::total_cmp);
+        let samples = seconds.len();
+        let mean = seconds.iter().sum::<f64>() / samples as f64;
+        let (std_dev, confidence) = if samples > 1 {
+            let squares: f64 = seconds.iter().map(|value| (value - mean).powi(2)).sum();
+            let std_dev = (squares / (samples - 1) as f64).sqrt();
+            (
+                std_dev,
+                t_95(samples - 1) * std_dev / (samples as f64).sqrt(),
+            )
+        } else {
+            (0., f64::INFINITY)
+        };
+
+        let lower_quartile = quantile(&seconds, 0.25);
+        let upper_quartile = quantile(&seconds, 0.75);
+        let range = upper_quartile - lower_quartile;
+        let mut outliers = Outliers::default();
+        for &value in &seconds {
+            let distance = (lower_quartile - value).max(value - upper_quartile);
+            if distance > 3. * range {
+                outliers.severe += 1;
+            } else if distance > 1.5 * range {
+                outliers.mild += 1;
+            }
+        }
+
+        Self {
+            samples,
+            min: seconds[0],
+            max: seconds[samples - 1],
+            mean,
+            median: quantile(&seconds, 0.5),
+            std_dev,
+            confidence,
+            outliers,
+        }
+    }
+
+    /// How many times faster these timings are than the baseline by their means, along with the standard deviation of that ratio.
+    pub fn speedup(&self, baseline: &Self) -> (f64, f64) {
+        let ratio = baseline.mean / self.mean;
+        let relative_error = ((self.std_dev / self.mean).powi(2)
+            + (baseline.std_dev / baseline.mean).powi(2))
+        .sqrt();
+        (ratio, ratio * relative_error)
+    }
+}
+
+/// How the timings of a version changed from earlier timings,
+/// by Welch's t-test of their means at the 5% significance level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// Significantly slower.
+    Regression,
+    /// Significantly faster.
+    Improvement,
+    /// No significant change, or too few timings to tell.
+    Insignificant,
+}
+
+impl Statistics {
+    /// How these timings changed from the earlier ones.
+    /// At least two timings of each are needed to tell a significant change.
+    pub fn change_from(&self, before: &Self) -> Change {
+        if self.samples < 2 || before.samples < 2 {
+            return Change::Insignificant;
+        }
+        let variance = self.std_dev.powi(2) / self.samples as f64;
+        let before_variance = before.std_dev.powi(2) / before.samples as f64;
+        let difference = self.mean - before.mean;
+        let significant = if variance + before_variance == 0. {
+            difference != 0.
+        } else {
+            // The Welch-Satterthwaite approximation, rounded down to be conservative.
+            let degrees_of_freedom = (variance + before_variance).powi(2)
+                / (variance.powi(2) / (self.samples - 1) as f64
+                    + before_variance.powi(2) / (before.samples - 1) as f64);
+            let t = difference / (variance + before_variance).sqrt();
+            t.abs() > t_95((degrees_of_freedom as usize).max(1))
+        };
+        match (significant, difference > 0.) {
+            (false, _) => Change::Insignificant,
+            (true, true) => Change::Regression,
+            (true, false) => Change::Improvement,
+        }
+    }
+}
+
+/// What a benchmark was run on, saved along with the timings so comparisons can point out differences.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// The commit checked out, ending in `-dirty` if there were uncommitted changes.
+    pub git_commit: Option<String>,
+    pub cpu_model: Option<String>,
+    pub num_threads: usize,
+    pub data_name: String,
+    /// The size of the data file in bytes.
+    pub data_size: u64,
+    pub max_bytes: Option<usize>,
+    pub mode: ParseMode,
+}
+
+impl Metadata {
+    /// The metadata of a benchmark run now from the current directory.
+    pub fn current(
+        num_threads: usize,
+        data_name: &str,
+        data_path: &Path,
+        max_bytes: Option<usize>,
+        mode: ParseMode,
+    ) -> Result<Self> {
+        let data_size = std::fs::metadata(data_path)
+            .with_context(|| format!("Could not read the size of {data_path:?}."))?
+            .len();
+        Ok(Self {
+            git_commit: git_commit(),
+            cpu_model: cpu_model(),
+            num_threads,
+            data_name: data_name.to_string(),
+            data_size,
+            max_bytes,
+            mode,
+        })
+    }
+
+    /// A description of each way the other metadata differs from this, which may explain differences in the timings.
+    pub fn differences(&self, other: &Self) -> Vec<String> {
+        let mut differences = vec![];
+        let mut compare = |what: &str, this: String, other: String| {
+            if this != other {
+                differences.push(format!("{what} was {this}, but is now {other}"));
+            }
+        };
+        compare(
+            "The CPU",
+            format!("{:?}", self.cpu_model),
+            format!("{:?}", other.cpu_model),
+        );
+        compare(
+            "The number of threads",
+            self.num_threads.to_string(),
+            other.num_threads.to_string(),
+        );
+        compare("The data", self.data_name.clone(), other.data_name.clone());
+        compare(
+            "The size of the data",
+            self.data_size.to_string(),
+            other.data_size.to_string(),
+        );
+        compare(
+            "max_bytes",
+            format!("{:?}", self.max_bytes),
+            format!("{:?}", other.max_bytes),
+        );
+        compare(
+            "The mode",
+            format!("{:?}", self.mode),
+            format!("{:?}", other.mode),
+        );
+        differences
+    }
+}
+
+/// The commit checked out in the current directory, if it is a git repository.
+fn git_commit() -> Option<String> {
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    let commit = git(&["rev-parse", "HEAD"])?;
+    let dirty = !git(&["status", "--porcelain", "--untracked-files=no"])?.is_empty();
+    Some(if dirty {
+        format!("{commit}-dirty")
+    } else {
+        commit
+    })
+}
+
+/// The model name of the first CPU in `/proc/cpuinfo`, so only on Linux.
+fn cpu_model() -> Option<String> {
+    let cpu_info = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpu_info
+        .lines()
+        .find(|line| line.starts_with("model name"))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, model)| model.trim().to_string())
+}
+
+/// The timings of a version in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionTimings {
+    pub version: String,
+    pub seconds: Vec<f64>,
+}
+
+impl VersionTimings {
+    pub fn new(version: &str, timings: &[Duration]) -> Self {
+        Self {
+            version: version.to_string(),
+            seconds: timings.iter().map(Duration::as_secs_f64).collect(),
+        }
+    }
+
+    pub fn statistics(&self) -> Statistics {
+        let timings: Vec<Duration> = self
+            .seconds
+            .iter()
+            .copied()
+            .map(Duration::from_secs_f64)
+            .collect();
+        Statistics::new(&timings)
+    }
+}
+
+/// A benchmark run as saved with `brc bench --save` and compared with `brc bench --compare`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedRun {
+    pub metadata: Metadata,
+    pub versions: Vec<VersionTimings>,
+}
+
+impl SavedRun {
+    /// Where the run saved under the given name is kept.
+    pub fn path(name: &str) -> PathBuf {
+        Path::new("data")
+            .join("bench")
+            .join(name)
+            .with_extension("json")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec_pretty(self).expect("A run should always be serializable.");
+        std::fs::write(path, json).with_context(|| format!("Could not save the run to {path:?}."))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read(path)
+            .with_context(|| format!("Could not read the saved run {path:?}."))?;
+        serde_json::from_slice(&json).with_context(|| format!("{path:?} is not a saved run."))
+    }
+
+    /// The timings of the version with the given name, if it was part of the run.
+    pub fn version(&self, name: &str) -> Option<&VersionTimings> {
+        self.versions.iter().find(|timings| timings.version == name)
+    }
+}
+
+/// The number of bytes and rows summarized with the given `max_bytes`.
+pub fn input_size(data: &[u8], max_bytes: Option<usize>) -> (u64, u64) {
+    let (used, _) = common::split_slices(data, max_bytes, 1);
+    let rows = used
+        .split(|&c| c == b'\n')
+        .filter(|line| !line.is_empty())
+        .count();
+    // The data used may not include the newline ending the last line.
+    let bytes = if used.is_empty() || used.ends_with(b"\n") {
+        used.len()
+    } else {
+        data.len().min(used.len() + 1)
+    };
+    (bytes as u64, rows as u64)
+}
+
+/// The timings of a version with one thread count and `max_bytes` in a sweep.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepPoint {
+    pub version: String,
+    pub num_threads: usize,
+    pub max_bytes: Option<usize>,
+    /// The number of bytes summarized, which is less than `max_bytes` if the data is smaller.
+    pub bytes: u64,
+    pub rows: u64,
+    pub statistics: Statistics,
+}
+
+impl SweepPoint {
+    /// The throughput by the mean time, counting a GB as 10^9 bytes.
+    pub fn gb_per_second(&self) -> f64 {
+        self.bytes as f64 / self.statistics.mean / 1e9
+    }
+
+    /// The throughput by the mean time.
+    pub fn rows_per_second(&self) -> f64 {
+        self.rows as f64 / self.statistics.mean
+    }
+}
+
+/// The parallel efficiency of each point, which is its speedup over the point of the same version and `max_bytes`
+/// with the fewest threads, divided by how many times as many threads it has.
+/// An efficiency of 1 means the throughput grows linearly with the threads.
+pub fn efficiencies(points: &[SweepPoint]) -> Vec<f64> {
+    points
+        .iter()
+        .map(|point| {
+            let base = points
+                .iter()
+                .filter(|base| base.version == point.version && base.max_bytes == point.max_bytes)
+                .min_by_key(|base| base.num_threads)
+                .unwrap();
+            (base.statistics.mean * base.num_threads as f64)
+                / (point.statistics.mean * point.num_threads as f64)
+        })
+        .collect()
+}
+
+/// The points of a sweep as CSV with a header, for plotting scaling curves.
+/// `max_bytes` is empty when all the data is used.
+pub fn sweep_csv(points: &[SweepPoint]) -> String {
+    let mut csv = String::from(
+        "version,threads,max_bytes,bytes,rows,repeats,mean_seconds,median_seconds,std_dev_seconds,gb_per_second,rows_per_second,efficiency\n",
+    );
+    for (point, efficiency) in points.iter().zip(efficiencies(points)) {
+        let max_bytes = point.max_bytes.map(|max_bytes| max_bytes.to_string());
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            point.version,
+            point.num_threads,
+            max_bytes.unwrap_or_default(),
+            point.bytes,
+            point.rows,
+            point.statistics.samples,
+            point.statistics.mean,
+            point.statistics.median,
+            point.statistics.std_dev,
+            point.gb_per_second(),
+            point.rows_per_second(),
+            efficiency,
+        ));
+    }
+    csv
+}
+
+/// The quantile of the sorted values, interpolating linearly between the closest two.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    sorted[below] + (sorted[above] - sorted[below]) * (position - below as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn statistics(seconds: &[f64]) -> Statistics {
+        let timings: Vec<Duration> = seconds
+            .iter()
+            .copied()
+            .map(Duration::from_secs_f64)
+            .collect();
+        Statistics::new(&timings)
+    }
+
+    #[test]
+    fn statistics_of_timings() {
+        let stats = statistics(&[3., 1., 2., 4.]);
+        assert_eq!((stats.samples, stats.min, stats.max), (4, 1., 4.));
+        assert!((stats.mean - 2.5).abs() < 1e-9);
+        assert!((stats.median - 2.5).abs() < 1e-9);
+        assert!((stats.std_dev - (5f64 / 3.).sqrt()).abs() < 1e-9);
+        assert!((stats.confidence - 3.182 * (5f64 / 3.).sqrt() / 2.).abs() < 1e-9);
+        assert_eq!(stats.outliers, Outliers::default());
+
+        let single = statistics(&[1.5]);
+        assert_eq!((single.median, single.std_dev), (1.5, 0.));
+        assert!(single.confidence.is_infinite());
+    }
+
+    #[test]
+    fn outliers() {
+        // The quartiles are 1.0 and 1.3, so the fences are at 0.55 and 0.1 below and 1.75 and 2.2 above.
+        let stats = statistics(&[1.0, 1.0, 1.1, 1.2, 1.3, 1.3, 1.9, 2.5, 0.6]);
+        assert_eq!(stats.outliers, Outliers { mild: 1, severe: 1 });
+    }
+
+    #[test]
+    fn change() {
+        let before = statistics(&[1.0, 1.1, 0.9, 1.0]);
+        let slower = statistics(&[1.5, 1.6, 1.4, 1.5]);
+        let noisy = statistics(&[0.5, 1.5, 0.8, 1.3]);
+        assert_eq!(slower.change_from(&before), Change::Regression);
+        assert_eq!(before.change_from(&slower), Change::Improvement);
+        assert_eq!(noisy.change_from(&before), Change::Insignificant);
+        assert_eq!(before.change_from(&before), Change::Insignificant);
+        assert_eq!(
+            statistics(&[5.]).change_from(&before),
+            Change::Insignificant
+        );
+    }
+
+    #[test]
+    fn saved_run() {
+        let run = SavedRun {
+            metadata: Metadata {
+                git_commit: Some("0123abc-dirty".to_string()),
+                cpu_model: None,
+                num_threads: 8,
+                data_name: "measurements".to_string(),
+                data_size: 13_000,
+                max_bytes: Some(1000),
+                mode: ParseMode::Lenient,
+            },
+            versions: vec![VersionTimings::new(
+                "v3",
+                &[Duration::from_millis(1250), Duration::from_nanos(3)],
+            )],
+        };
+        let json = serde_json::to_string(&run).unwrap();
+        assert_eq!(run, serde_json::from_str::<SavedRun>(&json).unwrap());
+        assert_eq!(run.version("v3").unwrap().statistics().max, 1.25);
+
+        let mut other = run.metadata.clone();
+        other.num_threads = 4;
+        assert_eq!(
+            run.metadata.differences(&other),
+            ["The number of threads was 8, but is now 4"]
+        );
+    }
+
+    #[test]
+    fn sweep() {
+        let data = b"a;1.0\nbb;2.0\nc;3.0\n";
+        assert_eq!(input_size(data, None), (19, 3));
+        assert_eq!(input_size(data, Some(7)), (13, 2));
+        assert_eq!(input_size(data, Some(0)), (0, 0));
+        assert_eq!(input_size(b"a;1.0", None), (5, 1));
+
+        let point = |num_threads, seconds| SweepPoint {
+            version: "v3".to_string(),
+            num_threads,
+            max_bytes: Some(2_000_000_000),
+            bytes: 2_000_000_000,
+            rows: 100_000_000,
+            statistics: statistics(&[seconds]),
+        };
+        let points = [point(1, 4.), point(2, 2.), point(4, 2.)];
+        assert_eq!(efficiencies(&points), [1., 1., 0.5]);
+        assert_eq!(points[1].gb_per_second(), 1.);
+        assert_eq!(points[1].rows_per_second(), 50_000_000.);
+        let csv = sweep_csv(&points);
+        assert_eq!(csv.lines().count(), 4);
+        assert_eq!(
+            csv.lines().nth(3).unwrap(),
+            "v3,4,2000000000,2000000000,100000000,1,2,2,0,1,50000000,0.5"
+        );
+    }
+
+    #[test]
+    fn speedup() {
+        let fast = statistics(&[1., 1.]);
+        let slow = statistics(&[2., 2.]);
+        assert_eq!(fast.speedup(&slow), (2., 0.));
+        assert_eq!(slow.speedup(&fast), (0.5, 0.));
+    }
+}
@@ -1,5 +1,23 @@
-use clap::{Args, Parser, Subcommand};
-use std::{io::Write, path::PathBuf, process::Command};
+use anyhow::{ensure, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use crate::{
+    bench::{
+        efficiencies, input_size, sweep_csv, Change, Metadata, SavedRun, Statistics, SweepPoint,
+        VersionTimings,
+    },
+    generate::{Generator, Profile},
+    input::{expand_globs, summarize_files, summarize_stream},
+    output::Column,
+    timing::{self, Phase, Timings},
+    Capabilities, OutputFormat, ParseMode, Summary, Version, VERSIONS,
+};
 
 #[derive(Parser, Debug, Clone)]
 pub struct Cli {
@@ -8,20 +26,244 @@ pub struct Cli {
 }
 
 impl Cli {
-    pub fn run(&self) {
+    pub fn run(&self) -> Result<()> {
         match &self.command {
-            Commands::Bench(bench) => bench.run(),
+            Commands::Run(run) => run.run()?,
+            Commands::Merge(merge) => merge.run()?,
+            Commands::Bench(bench) => bench.run()?,
             Commands::Base(base) => base.run(),
             Commands::Flame(flame) => flame.run(),
+            Commands::List => list(),
+            Commands::Gen(gen) => gen.run()?,
         }
+        Ok(())
     }
 }
 
 #[derive(Subcommand, Debug, Clone)]
 enum Commands {
+    /// Summarize one or more files, or standard input if the path is `-`, and print the result.
+    Run(Run),
+    /// Merge summaries saved with `run --summary` and print the result.
+    Merge(Merge),
     Bench(Bench),
     Base(Base),
     Flame(Flame),
+    /// List the versions along with their capabilities.
+    List,
+    /// Generate random measurements along with the expected output for `bench`.
+    Gen(Gen),
+}
+
+/// Parse the name of a version in the registry.
+fn parse_version(name: &str) -> Result<&'static Version, String> {
+    crate::version(name)
+        .ok_or_else(|| format!("There is no version named `{name}`. Run `brc list` to see them."))
+}
+
+fn list() {
+    let width = VERSIONS
+        .iter()
+        .map(|version| version.name.len())
+        .max()
+        .unwrap_or(0);
+    for version in VERSIONS {
+        let default = if version.name == crate::DEFAULT_VERSION {
+            " (default)"
+        } else {
+            ""
+        };
+        println!(
+            "{:<width$}  {}{default}",
+            version.name,
+            version.description.trim()
+        );
+        println!("{:<width$}  Capabilities: [{}]", "", version.capabilities);
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+struct Run {
+    /// Name of the version to use, see `brc list`.
+    /// Defaults to v3, or to vhist or vsketch when their histograms or sketches are needed,
+    /// or to vgeneric when the variance or standard deviation is.
+    #[arg(short = 'v', long, value_parser = parse_version)]
+    version: Option<&'static Version>,
+    /// Number of threads to use. Defaults to the number of logical cores.
+    #[arg(short = 'p', long)]
+    threads: Option<u32>,
+    #[arg(short = 'm', long, value_enum, default_value_t = ParseMode::Strict)]
+    mode: ParseMode,
+    /// Also print the summary of each file before the combined summary.
+    #[arg(long)]
+    per_file: bool,
+    /// Keep the exact histogram of each station, so the median and percentiles can be computed.
+    /// Implied by median and percentile columns. Saved summaries keep the histograms for later merging.
+    #[arg(long)]
+    histogram: bool,
+    /// Keep an approximate quantile sketch of each station instead of a histogram.
+    /// This allows values with any number of decimals, which are rounded to tenths for the other statistics.
+    #[arg(long, conflicts_with = "histogram")]
+    sketch: bool,
+    #[command(flatten)]
+    output: Output,
+    /// Save the combined summary to this path for merging it later.
+    /// It is stored as JSON if the extension is `json` and in a binary encoding otherwise.
+    #[arg(short = 's', long)]
+    summary: Option<PathBuf>,
+    /// Files or glob patterns to summarize.
+    #[arg(required = true)]
+    paths: Vec<String>,
+}
+
+impl Run {
+    pub fn run(&self) -> Result<()> {
+        let num_slices = self
+            .threads
+            .map_or_else(num_cpus::get, |threads| threads as usize);
+
+        let summarize_bytes = self.version()?.summarize_bytes;
+        if self.paths.iter().any(|path| path == "-") {
+            ensure!(
+                self.paths.len() == 1,
+                "Standard input cannot be combined with other paths."
+            );
+            let summary =
+                summarize_stream(std::io::stdin(), summarize_bytes, num_slices, self.mode)?;
+            return self.output.write(&summary, self.summary.as_deref());
+        }
+
+        let paths = expand_globs(&self.paths)?;
+        let summaries = summarize_files(&paths, summarize_bytes, num_slices, self.mode)?;
+        if self.per_file {
+            for (path, summary) in paths.iter().zip(&summaries) {
+                println!("==> {} <==", path.display());
+                self.output.print(summary);
+            }
+            println!("==> total <==");
+        }
+        let summary = summaries.into_iter().fold(Summary::new(), Summary::merge);
+        self.output.write(&summary, self.summary.as_deref())
+    }
+
+    /// The given version or the default one, making sure it keeps what the options need.
+    fn version(&self) -> Result<&'static Version> {
+        let (default, capable, needed): (_, fn(Capabilities) -> bool, _) = if self.sketch {
+            ("vsketch", |capabilities| capabilities.sketch, "sketches")
+        } else if self.histogram {
+            ("vhist", |capabilities| capabilities.histogram, "histograms")
+        } else if self.output.has_quantiles() {
+            (
+                "vhist",
+                |capabilities| capabilities.histogram || capabilities.sketch,
+                "histograms or sketches",
+            )
+        } else if self.output.needs_squares() {
+            (
+                "vgeneric",
+                |capabilities| capabilities.squares,
+                "sums of squares",
+            )
+        } else {
+            (crate::DEFAULT_VERSION, |_| true, "")
+        };
+        let version = self
+            .version
+            .unwrap_or_else(|| crate::version(default).expect("The default versions exist."));
+        ensure!(
+            capable(version.capabilities),
+            "Version {} does not keep {needed}, try {default}.",
+            version.name
+        );
+        // Every default version that keeps histograms or sketches also keeps sums of squares.
+        ensure!(
+            !self.output.needs_squares() || version.capabilities.squares,
+            "Version {} does not keep sums of squares, try {default}.",
+            version.name
+        );
+        Ok(version)
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+struct Merge {
+    #[command(flatten)]
+    output: Output,
+    /// Save the merged summary to this path, like `run --summary`.
+    #[arg(short = 's', long)]
+    summary: Option<PathBuf>,
+    /// Summaries to merge.
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+impl Merge {
+    pub fn run(&self) -> Result<()> {
+        let mut summary = Summary::new();
+        for path in &self.paths {
+            summary = summary.merge(Summary::load(path)?);
+        }
+        ensure!(
+            !self.output.has_quantiles()
+                || summary
+                    .iter()
+                    .all(|station| station.histogram().is_some() || station.sketch().is_some()),
+            "The median and percentiles need summaries saved with `run --histogram` or `run --sketch`."
+        );
+        ensure!(
+            !self.output.needs_squares()
+                || summary
+                    .iter()
+                    .all(|station| station.sum_squares_tenths().is_some()),
+            "The variance and standard deviation need summaries saved by a version keeping sums of squares, like `run --version vgeneric`."
+        );
+        self.output.write(&summary, self.summary.as_deref())
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+struct Output {
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// Statistics to write for each station, separated by commas.
+    /// One of min, mean, max, count, sum, variance, std_dev, median or a percentile like p95 or p99.9.
+    /// Defaults to min, mean and max, along with count in formats other than text.
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<Column>,
+}
+
+impl Output {
+    /// Whether any of the selected columns is a quantile, which needs the histogram or sketch of each station.
+    fn has_quantiles(&self) -> bool {
+        self.columns.iter().any(|column| column.is_quantile())
+    }
+
+    /// Whether any of the selected columns needs the sum of squares of each station.
+    fn needs_squares(&self) -> bool {
+        self.columns.iter().any(|column| column.needs_squares())
+    }
+
+    /// Print the summary and save it if a path is given.
+    fn write(&self, summary: &Summary, save_path: Option<&Path>) -> Result<()> {
+        if let Some(path) = save_path {
+            summary.save(path)?;
+        }
+        self.print(summary);
+        Ok(())
+    }
+
+    /// Print the summary to stdout and the number of skipped lines, if any, to stderr.
+    fn print(&self, summary: &Summary) {
+        if summary.skipped_lines().total() > 0 {
+            eprintln!("{}", summary.skipped_lines());
+        }
+        let columns = if self.columns.is_empty() {
+            self.format.default_columns()
+        } else {
+            &self.columns
+        };
+        print!("{}", summary.display_columns(self.format, columns));
+    }
 }
 
 #[derive(Args, Debug, Clone)]
@@ -34,8 +276,69 @@ struct Bench {
     num_threads: u32,
     #[arg(short = 'f', long, default_value = "measurements")]
     data_name: String,
-    #[arg(required = true)]
-    versions: Vec<u32>,
+    #[arg(short = 'm', long, value_enum, default_value_t = ParseMode::Strict)]
+    mode: ParseMode,
+    /// The version the others are compared to in the speedup table. Defaults to the first version.
+    /// It is benchmarked as well if it is not among the versions.
+    #[arg(short = 'b', long, value_parser = parse_version)]
+    baseline: Option<&'static Version>,
+    /// Save the timings along with the commit, CPU and arguments as `data/bench/NAME.json`.
+    #[arg(long, value_name = "NAME")]
+    save: Option<String>,
+    /// Compare with the run saved as NAME, failing if any version is significantly slower.
+    #[arg(long, value_name = "NAME")]
+    compare: Option<String>,
+    /// Sweep over these thread counts, running each in a thread pool of that size with as many slices.
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["save", "compare"])]
+    sweep_threads: Vec<usize>,
+    /// Sweep over these values of `max_bytes`. Underscores can be used as separators.
+    #[arg(long, value_delimiter = ',', value_parser = parse_count, conflicts_with_all = ["save", "compare"])]
+    sweep_bytes: Vec<u64>,
+    /// Where the sweep writes its results as CSV.
+    #[arg(long, default_value = "data/bench/sweep.csv")]
+    csv: PathBuf,
+    /// Names of the versions to compare, see `brc list`.
+    #[arg(required = true, value_parser = parse_version)]
+    versions: Vec<&'static Version>,
+}
+
+/// Print the mean time of each phase of each version, how imbalanced the slices and threads were and the page faults.
+fn print_phases(versions: &[&'static Version], phase_timings: &[Vec<Timings>], width: usize) {
+    println!(
+        "Phases in milliseconds, with the slowest slice and busiest thread relative to the mean:"
+    );
+    print!("{:<width$}", "version");
+    for phase in Phase::ALL {
+        print!("  {:>11}", phase.name());
+    }
+    println!("  {:>7}  {:>7}  page faults", "slice", "thread");
+    let mean = |values: Vec<f64>| values.iter().sum::<f64>() / values.len() as f64;
+    for (timings, version) in phase_timings.iter().zip(versions) {
+        print!("{:<width$}", version.name);
+        for phase in Phase::ALL {
+            let millis = mean(
+                timings
+                    .iter()
+                    .map(|t| t.phase(phase).as_secs_f64() * 1e3)
+                    .collect(),
+            );
+            print!("  {millis:>11.2}");
+        }
+        let slice = mean(
+            timings
+                .iter()
+                .filter_map(Timings::slice_imbalance)
+                .collect(),
+        );
+        let thread = mean(
+            timings
+                .iter()
+                .filter_map(Timings::thread_imbalance)
+                .collect(),
+        );
+        let page_faults = mean(timings.iter().map(|t| t.page_faults() as f64).collect());
+        println!("  {slice:>7.2}  {thread:>7.2}  {page_faults:.0}");
+    }
 }
 
 fn paths(data_name: &str, max_bytes: Option<usize>) -> (PathBuf, PathBuf) {
@@ -52,63 +355,298 @@ fn paths(data_name: &str, max_bytes: Option<usize>) -> (PathBuf, PathBuf) {
     (data_path, out_path)
 }
 
-fn result_to_out(result: &str) -> String {
-    result.replace(", ", "\n").replace(['{', '}'], "")
+fn result_to_out(result: &Summary) -> String {
+    result
+        .iter()
+        .map(|station| format!("{station}\n"))
+        .collect()
 }
 
 impl Bench {
-    pub fn run(&self) {
-        assert!(!self.versions.is_empty());
-        assert!(self.repeats > 0);
-        let (data_path, out_path) = paths(self.data_name.as_str(), self.max_bytes);
-        let expected = std::fs::read_to_string(out_path).unwrap();
-        // Get number of cpus available.
-        let num_slices = usize::try_from(self.num_threads).unwrap();
-
-        let version_funcs = crate::versions();
-        let versions = self
-            .versions
+    /// The versions to run, including the baseline, along with the index of the baseline.
+    fn versions(&self) -> (Vec<&'static Version>, usize) {
+        let mut versions = self.versions.clone();
+        let baseline = self.baseline.unwrap_or(versions[0]);
+        let baseline_index = match versions
             .iter()
-            .map(|&version_index| version_funcs[version_index as usize])
-            .collect::<Vec<_>>();
+            .position(|version| version.name == baseline.name)
+        {
+            Some(index) => index,
+            None => {
+                versions.push(baseline);
+                versions.len() - 1
+            }
+        };
+        (versions, baseline_index)
+    }
+
+    /// Run the versions interleaved for the given number of repeats, checking their output against
+    /// the expected output, which is the output of the first run if none is given.
+    /// Returns the runtimes of each version along with the timings of its phases, which are empty without the `timing` feature.
+    fn time_versions(
+        &self,
+        versions: &[&'static Version],
+        data_path: &Path,
+        max_bytes: Option<usize>,
+        num_slices: usize,
+        expected: &mut Option<String>,
+    ) -> (Vec<Vec<Duration>>, Vec<Vec<Timings>>) {
         let mut runtimes = vec![vec![]; versions.len()];
+        let mut phase_timings = vec![vec![]; versions.len()];
         for i in 0..self.repeats {
-            for (runtime_index, (version, &version_index)) in
-                versions.iter().zip(self.versions.iter()).enumerate()
-            {
+            for (runtime_index, version) in versions.iter().enumerate() {
                 print!(
-                "Repeat {i:>2}/{:<2}  Version {version_index}                                    \r",
-                self.repeats,
-            );
+                    "Repeat {i:>2}/{:<2}  Version {:<12}                          \r",
+                    self.repeats, version.name,
+                );
                 std::io::stdout().flush().unwrap();
+                // Drop anything recorded outside the run.
+                timing::take();
                 let start_time = std::time::Instant::now();
-                let result =
-                    std::hint::black_box(version(data_path.as_path(), self.max_bytes, num_slices))
-                        .unwrap();
+                let result = std::hint::black_box((version.summarize)(
+                    data_path, max_bytes, num_slices, self.mode,
+                ))
+                .unwrap();
                 let runtime = start_time.elapsed();
                 runtimes[runtime_index].push(runtime);
-                let result = result_to_out(result.as_str());
+                phase_timings[runtime_index].push(timing::take());
+                let result = result_to_out(&result);
+                let expected = expected.get_or_insert_with(|| result.clone());
                 result.lines().zip(expected.lines()).enumerate().for_each(
-                |(line_index, (out_line, expected))| {
-                    if out_line != expected {
-                        let output_path = data_path.with_extension("out.err");
-                        std::fs::write(output_path, &result).unwrap();
-                        panic!("Output for version {version_index} does not match expected on line {}.", line_index);
-                    }
-                },
+                    |(line_index, (out_line, expected))| {
+                        if out_line != expected {
+                            let output_path = data_path.with_extension("out.err");
+                            std::fs::write(output_path, &result).unwrap();
+                            panic!(
+                                "Output for version {} does not match expected on line {}.",
+                                version.name, line_index
+                            );
+                        }
+                    },
+                );
+            }
+        }
+        (runtimes, phase_timings)
+    }
+
+    pub fn run(&self) -> Result<()> {
+        assert!(!self.versions.is_empty());
+        assert!(self.repeats > 0);
+        if !self.sweep_threads.is_empty() || !self.sweep_bytes.is_empty() {
+            return self.sweep();
+        }
+        let (versions, baseline_index) = self.versions();
+        let (data_path, out_path) = paths(self.data_name.as_str(), self.max_bytes);
+        // Load the saved run first, so a wrong name fails before the benchmark.
+        let saved = self
+            .compare
+            .as_ref()
+            .map(|name| SavedRun::load(&SavedRun::path(name)))
+            .transpose()?;
+        let mut expected = Some(std::fs::read_to_string(out_path).unwrap());
+        // Get number of cpus available.
+        let num_slices = usize::try_from(self.num_threads).unwrap();
+
+        let (runtimes, phase_timings) = self.time_versions(
+            &versions,
+            &data_path,
+            self.max_bytes,
+            num_slices,
+            &mut expected,
+        );
+        println!("Results from {} repetitions in seconds:", self.repeats);
+
+        let width = versions
+            .iter()
+            .map(|version| version.name.len())
+            .max()
+            .unwrap()
+            .max("version".len());
+        let statistics: Vec<Statistics> = runtimes
+            .iter()
+            .map(|runtimes| Statistics::new(runtimes))
+            .collect();
+        println!(
+            "{:<width$}  {:>6}  {:>15}  {:>16}  {:>6}  {:>6}  outliers",
+            "version", "median", "mean ± std dev", "95% CI", "min", "max"
+        );
+        for (stats, version) in statistics.iter().zip(versions.iter()) {
+            let interval = format!(
+                "[{:.3}, {:.3}]",
+                stats.mean - stats.confidence,
+                stats.mean + stats.confidence
+            );
+            println!(
+                "{:<width$}  {:>6.3}  {:>6.3} ± {:<6.3}  {interval:>16}  {:>6.3}  {:>6.3}  {} mild, {} severe",
+                version.name,
+                stats.median,
+                stats.mean,
+                stats.std_dev,
+                stats.min,
+                stats.max,
+                stats.outliers.mild,
+                stats.outliers.severe,
+            );
+        }
+        if self.repeats == 1 {
+            println!("A single repetition gives no spread, use `-r` for more.");
+        }
+
+        let baseline = &statistics[baseline_index];
+        println!("Speedup relative to {}:", versions[baseline_index].name);
+        for (stats, version) in statistics.iter().zip(versions.iter()) {
+            let (speedup, error) = stats.speedup(baseline);
+            println!("{:<width$}  {speedup:.2} ± {error:.2}", version.name);
+        }
+        if cfg!(feature = "timing") {
+            print_phases(&versions, &phase_timings, width);
+        }
+
+        let metadata = Metadata::current(
+            num_slices,
+            &self.data_name,
+            &data_path,
+            self.max_bytes,
+            self.mode,
+        )?;
+        if let Some(name) = &self.save {
+            let run = SavedRun {
+                metadata: metadata.clone(),
+                versions: runtimes
+                    .iter()
+                    .zip(versions.iter())
+                    .map(|(runtimes, version)| VersionTimings::new(version.name, runtimes))
+                    .collect(),
+            };
+            let path = SavedRun::path(name);
+            run.save(&path)?;
+            println!("Saved the timings to {path:?}.");
+        }
+        if let (Some(name), Some(saved)) = (&self.compare, saved) {
+            println!(
+                "Compared to `{name}` from commit {}:",
+                saved.metadata.git_commit.as_deref().unwrap_or("unknown")
+            );
+            for difference in saved.metadata.differences(&metadata) {
+                println!("Note: {difference}.");
+            }
+            let mut regressions = vec![];
+            for (stats, version) in statistics.iter().zip(versions.iter()) {
+                let Some(before) = saved.version(version.name) else {
+                    println!("{:<width$}  not in the saved run", version.name);
+                    continue;
+                };
+                let before = before.statistics();
+                let change = stats.change_from(&before);
+                let verdict = match change {
+                    Change::Regression => "regression",
+                    Change::Improvement => "improvement",
+                    Change::Insignificant => "no significant change",
+                };
+                println!(
+                    "{:<width$}  {:.3} -> {:.3}  {:>+6.1}%  {verdict}",
+                    version.name,
+                    before.mean,
+                    stats.mean,
+                    (stats.mean / before.mean - 1.) * 100.,
+                );
+                if change == Change::Regression {
+                    regressions.push(version.name);
+                }
+            }
+            ensure!(
+                regressions.is_empty(),
+                "Significantly slower than `{name}`: {}.",
+                regressions.join(", ")
             );
+        }
+        Ok(())
+    }
+
+    /// Time every version for every combination of thread count and `max_bytes`,
+    /// printing the throughput and parallel efficiency and writing them as CSV.
+    /// Without a `.out` file for a `max_bytes`, the versions are checked against each other.
+    fn sweep(&self) -> Result<()> {
+        let (versions, _) = self.versions();
+        let thread_counts = if self.sweep_threads.is_empty() {
+            vec![usize::try_from(self.num_threads).unwrap()]
+        } else {
+            self.sweep_threads.clone()
+        };
+        let byte_limits: Vec<Option<usize>> = if self.sweep_bytes.is_empty() {
+            vec![self.max_bytes]
+        } else {
+            self.sweep_bytes
+                .iter()
+                .map(|&max_bytes| Some(usize::try_from(max_bytes).unwrap()))
+                .collect()
+        };
+        ensure!(
+            thread_counts.iter().all(|&num_threads| num_threads > 0),
+            "Thread counts should be positive."
+        );
+
+        let data_path = paths(self.data_name.as_str(), None).0;
+        let file = std::fs::File::open(&data_path)?;
+        let data = unsafe { memmap::Mmap::map(&file)? };
+        let mut points = vec![];
+        for &max_bytes in &byte_limits {
+            let (bytes, rows) = input_size(&data, max_bytes);
+            let out_path = paths(self.data_name.as_str(), max_bytes).1;
+            let mut expected = std::fs::read_to_string(out_path).ok();
+            for &num_threads in &thread_counts {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()?;
+                let (runtimes, _) = pool.install(|| {
+                    self.time_versions(&versions, &data_path, max_bytes, num_threads, &mut expected)
+                });
+                points.extend(
+                    runtimes
+                        .iter()
+                        .zip(versions.iter())
+                        .map(|(runtimes, version)| SweepPoint {
+                            version: version.name.to_string(),
+                            num_threads,
+                            max_bytes,
+                            bytes,
+                            rows,
+                            statistics: Statistics::new(runtimes),
+                        }),
+                );
             }
         }
-        println!("Results from {} repetitions:", self.repeats);
 
-        for (runtimes, &version_index) in runtimes.iter().zip(self.versions.iter()) {
-            assert_eq!(runtimes.len(), self.repeats as usize);
-            let min_time = runtimes.iter().min().unwrap().as_secs_f32();
-            let max_time = runtimes.iter().max().unwrap().as_secs_f32();
-            let total_time = runtimes.iter().sum::<std::time::Duration>().as_secs_f32();
-            let average_time = total_time / self.repeats as f32;
-            println!("V{version_index}: {min_time:.2} / {average_time:.2} / {max_time:.2}",);
+        let width = versions
+            .iter()
+            .map(|version| version.name.len())
+            .max()
+            .unwrap()
+            .max("version".len());
+        println!(
+            "{:<width$}  {:>7}  {:>13}  {:>8}  {:>6}  {:>12}  efficiency",
+            "version", "threads", "max_bytes", "mean (s)", "GB/s", "rows/s"
+        );
+        let efficiencies = efficiencies(&points);
+        for (point, efficiency) in points.iter().zip(&efficiencies) {
+            let max_bytes = point
+                .max_bytes
+                .map_or_else(|| "all".to_string(), |max_bytes| max_bytes.to_string());
+            println!(
+                "{:<width$}  {:>7}  {max_bytes:>13}  {:>8.3}  {:>6.2}  {:>12.0}  {efficiency:.2}",
+                point.version,
+                point.num_threads,
+                point.statistics.mean,
+                point.gb_per_second(),
+                point.rows_per_second(),
+            );
+        }
+        if let Some(parent) = self.csv.parent() {
+            std::fs::create_dir_all(parent)?;
         }
+        std::fs::write(&self.csv, sweep_csv(&points))?;
+        println!("Wrote the sweep to {:?}.", self.csv);
+        Ok(())
     }
 }
 
@@ -120,8 +658,11 @@ struct Base {
     num_threads: u32,
     #[arg(short = 'f', long, default_value = "measurements")]
     data_name: String,
-    #[arg(required = true)]
-    version: u32,
+    #[arg(short = 'm', long, value_enum, default_value_t = ParseMode::Strict)]
+    mode: ParseMode,
+    /// Name of the version to use, see `brc list`.
+    #[arg(required = true, value_parser = parse_version)]
+    version: &'static Version,
 }
 
 impl Base {
@@ -134,16 +675,71 @@ impl Base {
         // Get number of cpus available.
         let num_slices = usize::try_from(self.num_threads).unwrap();
 
-        let version = crate::versions()[self.version as usize];
-        let result = version(data_path.as_path(), self.max_bytes, num_slices).unwrap();
+        let result =
+            (self.version.summarize)(data_path.as_path(), self.max_bytes, num_slices, self.mode)
+                .unwrap();
+        if result.skipped_lines().total() > 0 {
+            eprintln!("{}", result.skipped_lines());
+        }
 
-        let result = result_to_out(result.as_str());
+        let result = result_to_out(&result);
 
         std::fs::write(out_path.as_path(), result).unwrap();
         println!("Base line output written to {out_path:?}",);
     }
 }
 
+/// Parse a number that may use underscores as separators, like `1_000_000_000`.
+fn parse_count(count: &str) -> Result<u64, String> {
+    count
+        .replace('_', "")
+        .parse()
+        .map_err(|error| format!("{error}"))
+}
+
+#[derive(Args, Debug, Clone)]
+struct Gen {
+    /// Number of rows to generate. Underscores can be used as separators.
+    #[arg(short = 'n', long, value_parser = parse_count, default_value = "1_000_000_000")]
+    rows: u64,
+    /// Number of distinct stations. Defaults to 413, or 10000 for most of the adversarial profiles.
+    #[arg(short = 'k', long)]
+    stations: Option<usize>,
+    /// The kind of data to generate.
+    #[arg(long, value_enum, default_value_t = Profile::Realistic)]
+    profile: Profile,
+    /// Seed of the random data. The same seed always gives the same data.
+    #[arg(short = 's', long, default_value = "0")]
+    seed: u64,
+    /// Path of the data. The expected output is written next to it with the extension `out`.
+    #[arg(short = 'o', long, default_value = "data/measurements.txt")]
+    out: PathBuf,
+}
+
+impl Gen {
+    pub fn run(&self) -> Result<()> {
+        let stations = self
+            .stations
+            .unwrap_or_else(|| self.profile.default_stations());
+        let generator = Generator::new(self.profile, stations, self.seed)?;
+        if let Some(parent) = self.out.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let start_time = std::time::Instant::now();
+        let file = std::fs::File::create(&self.out)?;
+        let summary = generator.write(self.rows, std::io::BufWriter::new(file))?;
+        let out_path = self.out.with_extension("out");
+        std::fs::write(&out_path, result_to_out(&summary))?;
+        println!(
+            "Wrote {} rows to {:?} and the expected output to {out_path:?} in {:.2}s.",
+            self.rows,
+            self.out,
+            start_time.elapsed().as_secs_f32()
+        );
+        Ok(())
+    }
+}
+
 #[derive(Args, Debug, Clone)]
 struct Flame {
     #[arg(short='n', long, default_value=None)]
@@ -154,8 +750,11 @@ struct Flame {
     num_threads: u32,
     #[arg(short = 'f', long, default_value = "measurements")]
     data_name: String,
-    #[arg(required = true)]
-    version: u32,
+    #[arg(short = 'm', long, value_enum, default_value_t = ParseMode::Strict)]
+    mode: ParseMode,
+    /// Name of the version to use, see `brc list`.
+    #[arg(required = true, value_parser = parse_version)]
+    version: &'static Version,
 }
 
 impl Flame {
@@ -175,13 +774,15 @@ impl Flame {
             "record",
             "target/release/brc",
             "bench",
-            &format!("{}", self.version),
+            self.version.name,
             "-r",
             &format!("{}", self.repeats),
             "-p",
             &format!("{}", self.num_threads),
             "-f",
             &self.data_name,
+            "-m",
+            self.mode.to_possible_value().unwrap().get_name(),
         ]);
         if let Some(max_bytes) = self.max_bytes {
             command.args(["-n", &format!("{max_bytes}")]);
@@ -0,0 +1,356 @@
+//! The building blocks shared by the versions, which mostly differ in how they parse a slice.
+//!
+//! Every version runs the same pipeline:
+//! 1. The input is split into one slice per thread at line boundaries by `split_slices`.
+//! 2. Each slice is parsed into a table of stations by the `summarize_slice` function of the version.
+//!    This is the part the versions vary, using `find_delimiter_long`, `hash_str` and `update_station` from here as they like.
+//! 3. The tables of the slices are merged in the order of the input by `Table::merge` in `summarize_slices`,
+//!    which also reports the first error in the input.
+//! 4. The merged table is turned into the output of its aggregators by `Summary::into_result`,
+//!    and from there into a `crate::Summary`, which handles the output formats for every version.
+//!
+//! With the `timing` feature, each of these phases is timed by `crate::timing`.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    hash::{BuildHasher, Hasher},
+    path::Path,
+};
+
+use anyhow::{ensure, Result};
+use itertools::Itertools;
+use memmap::MmapOptions;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rustc_hash::FxHasher;
+
+use crate::{
+    aggregator::{Aggregated, Aggregator, Stats},
+    error::ParseError,
+    timing::{self, Phase},
+    SkippedLines,
+};
+
+/// A station in the table of a slice along with the aggregator of its values.
+#[derive(Debug, Clone)]
+pub(crate) struct SummaryEntry<'a, A> {
+    pub(crate) name: &'a str,
+    pub(crate) aggregator: A,
+}
+
+/// The table of a version for a single slice, which can be merged with the tables of other slices.
+pub(crate) trait Table: Send {
+    /// Combines the tables of two slices, where `other` comes after `self` in the input.
+    fn merge(self, other: Self) -> Self;
+}
+
+impl Table for crate::Summary {
+    fn merge(self, other: Self) -> Self {
+        crate::Summary::merge(self, other)
+    }
+}
+
+/// The aggregators of the stations in a slice, sorted by station name.
+pub(crate) struct Summary<'a, A> {
+    data: Vec<SummaryEntry<'a, A>>,
+}
+
+impl<'a, A: Aggregator> Summary<'a, A> {
+    pub(crate) fn new() -> Self {
+        Self { data: vec![] }
+    }
+
+    /// Creates a new summary from entries in any order, each with a distinct name.
+    pub(crate) fn from_entries(mut data: Vec<SummaryEntry<'a, A>>) -> Self {
+        data.sort_by_key(|entry| entry.name);
+        Self { data }
+    }
+
+    /// Creates a new summary from a hashmap.
+    /// The result is sorted by station name.
+    pub(crate) fn from_hashmap<S>(data: HashMap<u64, SummaryEntry<'a, A>, S>) -> Self {
+        Self::from_entries(data.into_values().collect())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Convert the summary into the final output.
+    pub(crate) fn into_result(self, skipped_lines: SkippedLines) -> Aggregated<A::Output> {
+        let stations = self
+            .data
+            .into_iter()
+            .map(|entry| (entry.name.to_string(), entry.aggregator.finish()))
+            .collect();
+        Aggregated::new(stations, skipped_lines)
+    }
+}
+
+impl<A: Aggregator + Send> Table for Summary<'_, A> {
+    fn merge(self, other: Self) -> Self {
+        let mut result = Vec::with_capacity(self.data.len().max(other.data.len()));
+        let mut a_iter = self.data.into_iter();
+        let mut b_iter = other.data.into_iter();
+
+        let mut cur_a = a_iter.next();
+        let mut cur_b = b_iter.next();
+        loop {
+            if let Some(mut a) = cur_a {
+                if let Some(b) = cur_b {
+                    // The aggregators need not be `Copy`, so whichever entry is not used is put back.
+                    match a.name.cmp(b.name) {
+                        Ordering::Less => {
+                            result.push(a);
+                            cur_a = a_iter.next();
+                            cur_b = Some(b);
+                        }
+                        Ordering::Equal => {
+                            a.aggregator.merge(&b.aggregator);
+                            result.push(a);
+                            cur_a = a_iter.next();
+                            cur_b = b_iter.next();
+                        }
+                        Ordering::Greater => {
+                            result.push(b);
+                            cur_a = Some(a);
+                            cur_b = b_iter.next();
+                        }
+                    }
+                } else {
+                    result.push(a);
+                    result.extend(a_iter);
+                    break;
+                }
+            } else {
+                result.extend(cur_b.into_iter().chain(b_iter));
+                break;
+            }
+        }
+        Self { data: result }
+    }
+}
+
+/// Given a 16-byte word, find the position of the first semicolon byte.
+/// HOT
+pub(crate) fn find_delimiter_long<const DELIM: u8>(word: u128) -> u8 {
+    const SPREADER: u128 = 0x0101_0101_0101_0101_0101_0101_0101_0101;
+    let delim_pattern: u128 = DELIM as u128 * SPREADER;
+    let input = word ^ delim_pattern;
+    let processed_input = input.wrapping_sub(SPREADER) & !input & (0x80 * SPREADER);
+    processed_input.trailing_zeros() as u8 >> 3 // The position of the first ; byte, or 16 if there is none.
+}
+
+/// Hash a string slice into a u64 using the FxHash algorithm.
+/// HOT
+pub(crate) fn hash_str(s: &[u8]) -> u64 {
+    let mut hash = FxHasher::default();
+
+    hash.write(s);
+    hash.finish()
+}
+
+/// Given a arbitrary index into the slice, find the first following index that is a works as a split index.
+/// This means finding the first newline character after the given index, or the end of the slice if there is none.
+pub(crate) fn find_split_index(slice: &[u8], index: usize) -> usize {
+    assert!(index <= slice.len());
+    if index == 0 {
+        return index;
+    }
+    let mut split_index = index;
+    while split_index < slice.len() && slice[split_index] != b'\n' {
+        split_index += 1;
+    }
+    split_index + 1
+}
+
+/// Update the entry for the station with the given name and hash, adding it as a clone of `prototype` if it is not present.
+/// Returns false without updating anything if the name is not a valid station name.
+/// HOT
+#[inline(always)]
+pub(crate) fn update_station<'a, A: Aggregator, S: BuildHasher>(
+    data: &mut HashMap<u64, SummaryEntry<'a, A>, S>,
+    hash: u64,
+    name: &'a [u8],
+    value: i32,
+    prototype: &A,
+) -> bool {
+    // Distinct names can share a hash, so on a name mismatch the following keys are probed.
+    let mut key = hash;
+    loop {
+        if let Some(city_data) = data.get_mut(&key) {
+            if city_data.name.as_bytes() == name {
+                city_data.aggregator.update(value);
+                return true;
+            }
+            key = key.wrapping_add(1);
+        } else {
+            // New names are validated here, so invalid names never make it into the table.
+            let Ok(name) = std::str::from_utf8(name) else {
+                return false;
+            };
+            if name.contains('\n') {
+                return false;
+            }
+            let mut aggregator = prototype.clone();
+            aggregator.update(value);
+            data.insert(key, SummaryEntry { name, aggregator });
+            return true;
+        }
+    }
+}
+
+/// The table of a slice along with the invalid lines skipped in it, or the first error in it.
+pub(crate) type SliceResult<T> = Result<(T, SkippedLines), ParseError>;
+
+/// Split the data into the given number of slices at line boundaries, returning the data that is used
+/// along with the slices and where they start in it.
+/// If `max_bytes` is given, only the lines starting before that point are used.
+pub(crate) fn split_slices(
+    data: &[u8],
+    max_bytes: Option<usize>,
+    num_slices: usize,
+) -> (&[u8], Vec<(usize, &[u8])>) {
+    let len = find_split_index(data, data.len().min(max_bytes.unwrap_or(usize::MAX)));
+    let total_slice = &data[..len.saturating_sub(1)];
+
+    let slices = (0..=num_slices)
+        .map(|i| find_split_index(total_slice, (total_slice.len() * i) / num_slices))
+        .tuple_windows()
+        .map(|(start, end)| {
+            if start == end {
+                // Both are past the end of the data if the last line has no newline.
+                (start, &total_slice[..0])
+            } else {
+                (start, &total_slice[start..(end - 1)])
+            }
+        })
+        .collect();
+    (total_slice, slices)
+}
+
+/// Parse slices of the data in parallel with the given parser and merge the resulting tables in order,
+/// reporting the first error in the input if there is one.
+/// If `max_bytes` is given, only the lines starting before that point are included.
+pub(crate) fn summarize_slices<'a, T: Table>(
+    data: &'a [u8],
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    summarize_slice: impl Fn(&'a [u8]) -> SliceResult<T> + Sync,
+) -> Result<(T, SkippedLines)> {
+    ensure!(num_slices > 0, "There should be at least one slice.");
+    let (total_slice, slices) =
+        timing::time(Phase::Split, || split_slices(data, max_bytes, num_slices));
+    let summaries: Vec<SliceResult<T>> = timing::time(Phase::Slices, || {
+        slices
+            .into_par_iter()
+            .map(|(start, slice)| {
+                timing::time_slice(slice.len(), || summarize_slice(slice))
+                    .map_err(|error| error.offset_by(&total_slice[..start]))
+            })
+            .collect()
+    });
+    let summaries = summaries.into_iter().collect::<Result<Vec<_>, _>>()?;
+    Ok(timing::time(Phase::Merge, || {
+        summaries
+            .into_iter()
+            .reduce(|(a, a_skipped), (b, b_skipped)| (a.merge(b), a_skipped.merge(b_skipped)))
+            .expect("There is at least one slice.")
+    }))
+}
+
+/// Run the whole pipeline on data that is already in memory with a parser giving a `Summary` for each slice,
+/// giving the output of the aggregator for each station.
+pub(crate) fn aggregate_bytes<'a, A: Aggregator + Send>(
+    data: &'a [u8],
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    summarize_slice: impl Fn(&'a [u8]) -> SliceResult<Summary<'a, A>> + Sync,
+) -> Result<Aggregated<A::Output>> {
+    let (summary, skipped_lines) = summarize_slices(data, max_bytes, num_slices, summarize_slice)?;
+    Ok(timing::time(Phase::IntoResult, || {
+        summary.into_result(skipped_lines)
+    }))
+}
+
+/// Run the whole pipeline on data that is already in memory with a parser computing the usual `Stats` of each station.
+pub(crate) fn summarize_bytes<'a>(
+    data: &'a [u8],
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    summarize_slice: impl Fn(&'a [u8]) -> SliceResult<Summary<'a, Stats>> + Sync,
+) -> Result<crate::Summary> {
+    let result = aggregate_bytes(data, max_bytes, num_slices, summarize_slice)?;
+    Ok(result.into_summary(|name, stats| stats.into_station_stats(name)))
+}
+
+/// Memory map the file at the given path and summarize it with `summarize_bytes`.
+pub(crate) fn summarize_file(
+    path: &Path,
+    summarize_bytes: impl FnOnce(&[u8]) -> Result<crate::Summary>,
+) -> Result<crate::Summary> {
+    timing::count_page_faults(|| {
+        let file = timing::time(Phase::Map, || -> Result<_> {
+            let file = std::fs::File::open(path)?;
+            if file.metadata()?.len() == 0 {
+                // Empty files cannot be mapped.
+                return Ok(None);
+            }
+            Ok(Some(unsafe { MmapOptions::new().map(&file)? }))
+        })?;
+        summarize_bytes(file.as_deref().unwrap_or_default())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Bytes from a small alphabet, so lines, semicolons and empty lines are common.
+    fn data() -> impl Strategy<Value = Vec<u8>> {
+        proptest::collection::vec(prop::sample::select(b"ab;\n".to_vec()), 0..200)
+    }
+
+    /// The non-empty lines of the slice.
+    fn lines(slice: &[u8]) -> Vec<&[u8]> {
+        slice
+            .split(|&c| c == b'\n')
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn split_index(data in data(), index in any::<prop::sample::Index>()) {
+            let index = index.index(data.len() + 1);
+            let split_index = find_split_index(&data, index);
+            if index == 0 {
+                prop_assert_eq!(split_index, 0);
+            } else {
+                // Right after the first newline from the index, or one past the end of the data if there is none.
+                let newline = data[index..]
+                    .iter()
+                    .position(|&c| c == b'\n')
+                    .map_or(data.len(), |position| index + position);
+                prop_assert_eq!(split_index, newline + 1);
+            }
+        }
+
+        #[test]
+        fn slices(data in data(), num_slices in 1..=17usize) {
+            let (total_slice, slices) = split_slices(&data, None, num_slices);
+            prop_assert_eq!(total_slice, &data[..]);
+            prop_assert_eq!(slices.len(), num_slices);
+            for &(start, slice) in slices.iter().filter(|(_, slice)| !slice.is_empty()) {
+                prop_assert_eq!(&total_slice[start..start + slice.len()], slice);
+                prop_assert!(start == 0 || total_slice[start - 1] == b'\n');
+            }
+            // No line is cut in two or left out.
+            let slice_lines: Vec<&[u8]> = slices.iter().flat_map(|&(_, slice)| lines(slice)).collect();
+            prop_assert_eq!(slice_lines, lines(total_slice));
+        }
+    }
+}
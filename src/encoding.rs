@@ -0,0 +1,389 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    histogram::Histogram, sketch::QuantileSketch, ParseErrorReason, SkippedLines, StationStats,
+    Summary,
+};
+
+/// Start of every summary in the binary encoding.
+const MAGIC: &[u8; 6] = b"BRCSUM";
+/// Version of both encodings, to be increased whenever either of them changes.
+const FORMAT_VERSION: u8 = 1;
+
+/// The ways a `Summary` can be stored on disk.
+/// Both keep the exact statistics, so merging stored summaries gives the same result as a single pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Encoding {
+    /// A compact little-endian binary encoding.
+    #[default]
+    Binary,
+    /// A JSON object with temperatures in tenths of a degree.
+    Json,
+}
+
+impl Encoding {
+    /// The encoding to use for the given path, which is JSON if the extension is `json` and binary otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            Self::Json
+        } else {
+            Self::Binary
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SummaryRecord {
+    format_version: u8,
+    stations: Vec<StationRecord>,
+    skipped_lines: BTreeMap<ParseErrorReason, u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StationRecord {
+    name: String,
+    min_tenths: i32,
+    max_tenths: i32,
+    sum_tenths: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sum_squares_tenths: Option<u64>,
+    count: u64,
+    /// Pairs of a value in tenths of a degree and its number of measurements, sorted by value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    histogram: Option<Vec<(i32, u64)>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sketch: Option<SketchRecord>,
+}
+
+impl StationRecord {
+    /// Check the decoded statistics of a station, since `StationStats::new` panics on a zero count.
+    fn into_stats(self) -> Result<StationStats> {
+        let name = self.name;
+        let count = self.count;
+        if count == 0 || self.min_tenths > self.max_tenths {
+            bail!("The statistics of station {name:?} are invalid.");
+        }
+        let mut stats = StationStats::new(
+            name,
+            self.min_tenths,
+            self.max_tenths,
+            self.sum_tenths,
+            count,
+        );
+        if let Some(sum_squares) = self.sum_squares_tenths {
+            stats = stats.with_sum_squares(sum_squares);
+        }
+        if let Some(bins) = self.histogram {
+            stats = match Histogram::from_bins(bins) {
+                Some(histogram) if histogram.count() == count => stats.with_histogram(histogram),
+                _ => bail!("The histogram of station {:?} is invalid.", stats.name()),
+            };
+        }
+        if let Some(sketch) = self.sketch {
+            stats =
+                match QuantileSketch::from_parts(sketch.k, sketch.min, sketch.max, sketch.levels) {
+                    Some(sketch) if sketch.count() == count => stats.with_sketch(sketch),
+                    _ => bail!("The sketch of station {:?} is invalid.", stats.name()),
+                };
+        }
+        Ok(stats)
+    }
+}
+
+/// The parts of a `QuantileSketch`, with the values as they are rather than in tenths.
+#[derive(Serialize, Deserialize)]
+struct SketchRecord {
+    k: u16,
+    min: f64,
+    max: f64,
+    levels: Vec<Vec<f64>>,
+}
+
+impl SketchRecord {
+    fn new(sketch: &QuantileSketch) -> Self {
+        Self {
+            k: sketch.k(),
+            min: sketch.min(),
+            max: sketch.max(),
+            levels: sketch.levels().to_vec(),
+        }
+    }
+}
+
+/// Reads values from the binary encoding, failing if the data ends early.
+struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take_slice(N)?.try_into().unwrap())
+    }
+
+    fn take_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(len <= self.data.len(), "The summary ends unexpectedly.");
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(value)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        self.take().map(f64::from_le_bytes)
+    }
+
+    /// Reads a flag telling whether an optional value is present.
+    fn flag(&mut self) -> Result<bool> {
+        match self.take()? {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => bail!("A flag in the summary is invalid."),
+        }
+    }
+}
+
+impl Summary {
+    /// Encode the summary for storing it on disk.
+    pub fn encode(&self, encoding: Encoding) -> Vec<u8> {
+        match encoding {
+            Encoding::Binary => self.encode_binary(),
+            Encoding::Json => {
+                let record = SummaryRecord {
+                    format_version: FORMAT_VERSION,
+                    stations: self
+                        .iter()
+                        .map(|station| StationRecord {
+                            name: station.name().to_string(),
+                            min_tenths: station.min_tenths(),
+                            max_tenths: station.max_tenths(),
+                            sum_tenths: station.sum_tenths(),
+                            sum_squares_tenths: station.sum_squares_tenths(),
+                            count: station.count(),
+                            histogram: station
+                                .histogram()
+                                .map(|histogram| histogram.bins().to_vec()),
+                            sketch: station.sketch().map(SketchRecord::new),
+                        })
+                        .collect(),
+                    skipped_lines: self.skipped_lines().iter().collect(),
+                };
+                serde_json::to_vec_pretty(&record)
+                    .expect("A summary should always be serializable.")
+            }
+        }
+    }
+
+    fn encode_binary(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.push(FORMAT_VERSION);
+        data.push(ParseErrorReason::ALL.len() as u8);
+        for reason in ParseErrorReason::ALL {
+            data.extend(self.skipped_lines().get(reason).to_le_bytes());
+        }
+        data.extend((self.len() as u64).to_le_bytes());
+        for station in self {
+            data.extend((station.name().len() as u64).to_le_bytes());
+            data.extend(station.name().as_bytes());
+            data.extend(station.min_tenths().to_le_bytes());
+            data.extend(station.max_tenths().to_le_bytes());
+            data.extend(station.sum_tenths().to_le_bytes());
+            match station.sum_squares_tenths() {
+                Some(sum_squares) => {
+                    data.push(1);
+                    data.extend(sum_squares.to_le_bytes());
+                }
+                None => data.push(0),
+            }
+            data.extend(station.count().to_le_bytes());
+            match station.histogram() {
+                Some(histogram) => {
+                    data.push(1);
+                    data.extend((histogram.bins().len() as u64).to_le_bytes());
+                    for &(value, count) in histogram.bins() {
+                        data.extend(value.to_le_bytes());
+                        data.extend(count.to_le_bytes());
+                    }
+                }
+                None => data.push(0),
+            }
+            match station.sketch() {
+                Some(sketch) => {
+                    data.push(1);
+                    data.extend(sketch.k().to_le_bytes());
+                    data.extend(sketch.min().to_le_bytes());
+                    data.extend(sketch.max().to_le_bytes());
+                    data.extend((sketch.levels().len() as u64).to_le_bytes());
+                    for items in sketch.levels() {
+                        data.extend((items.len() as u64).to_le_bytes());
+                        for item in items {
+                            data.extend(item.to_le_bytes());
+                        }
+                    }
+                }
+                None => data.push(0),
+            }
+        }
+        data
+    }
+
+    /// Decode a summary in either encoding, detecting which one is used.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.starts_with(MAGIC) {
+            Self::decode_binary(&data[MAGIC.len()..])
+        } else {
+            let record: SummaryRecord =
+                serde_json::from_slice(data).context("The summary is neither binary nor JSON.")?;
+            ensure!(
+                record.format_version == FORMAT_VERSION,
+                "Unsupported summary format version {}.",
+                record.format_version
+            );
+            let mut skipped_lines = SkippedLines::new();
+            for (reason, count) in record.skipped_lines {
+                skipped_lines.add(reason, count);
+            }
+            let stations = record
+                .stations
+                .into_iter()
+                .map(StationRecord::into_stats)
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Self::from_stations(stations).with_skipped_lines(skipped_lines))
+        }
+    }
+
+    fn decode_binary(data: &[u8]) -> Result<Self> {
+        let mut decoder = Decoder { data };
+        let [format_version] = decoder.take()?;
+        ensure!(
+            format_version == FORMAT_VERSION,
+            "Unsupported summary format version {format_version}."
+        );
+        let [num_reasons] = decoder.take()?;
+        ensure!(
+            num_reasons as usize == ParseErrorReason::ALL.len(),
+            "The summary has an unexpected number of skip reasons."
+        );
+        let mut skipped_lines = SkippedLines::new();
+        for reason in ParseErrorReason::ALL {
+            skipped_lines.add(reason, decoder.u64()?);
+        }
+        let num_stations = decoder.u64()?;
+        let mut stations = vec![];
+        for _ in 0..num_stations {
+            let name_len = decoder.u64()?;
+            let name = decoder.take_slice(usize::try_from(name_len)?)?;
+            let name = std::str::from_utf8(name).context("A station name is not valid UTF-8.")?;
+            let min_tenths = decoder.take().map(i32::from_le_bytes)?;
+            let max_tenths = decoder.take().map(i32::from_le_bytes)?;
+            let sum_tenths = decoder.take().map(i64::from_le_bytes)?;
+            let sum_squares_tenths = if decoder.flag()? {
+                Some(decoder.u64()?)
+            } else {
+                None
+            };
+            let count = decoder.u64()?;
+            let histogram = if decoder.flag()? {
+                let num_bins = decoder.u64()?;
+                let mut bins = vec![];
+                for _ in 0..num_bins {
+                    bins.push((decoder.take().map(i32::from_le_bytes)?, decoder.u64()?));
+                }
+                Some(bins)
+            } else {
+                None
+            };
+            let sketch = if decoder.flag()? {
+                let k = decoder.take().map(u16::from_le_bytes)?;
+                let min = decoder.f64()?;
+                let max = decoder.f64()?;
+                let num_levels = decoder.u64()?;
+                let mut levels = vec![];
+                for _ in 0..num_levels {
+                    let num_items = decoder.u64()?;
+                    let mut items = vec![];
+                    for _ in 0..num_items {
+                        items.push(decoder.f64()?);
+                    }
+                    levels.push(items);
+                }
+                Some(SketchRecord {
+                    k,
+                    min,
+                    max,
+                    levels,
+                })
+            } else {
+                None
+            };
+            let record = StationRecord {
+                name: name.to_string(),
+                min_tenths,
+                max_tenths,
+                sum_tenths,
+                sum_squares_tenths,
+                count,
+                histogram,
+                sketch,
+            };
+            stations.push(record.into_stats()?);
+        }
+        ensure!(decoder.data.is_empty(), "The summary has trailing data.");
+        Ok(Self::from_stations(stations).with_skipped_lines(skipped_lines))
+    }
+
+    /// Write the summary to the given path, using the encoding given by `Encoding::from_path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.encode(Encoding::from_path(path)))
+            .with_context(|| format!("Failed to write summary to {path:?}."))
+    }
+
+    /// Read a summary written by `save` in either encoding.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).with_context(|| format!("Failed to read {path:?}."))?;
+        Self::decode(&data).with_context(|| format!("Failed to decode summary in {path:?}."))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut skipped_lines = SkippedLines::new();
+        skipped_lines.add(ParseErrorReason::EmptyValue, 3);
+        let summary = Summary::from_stations([
+            StationStats::new("Petropavlovsk-Kamchatsky", -95, 95, 0, 2)
+                .with_sum_squares(18050)
+                .with_histogram(Histogram::from_bins(vec![(-95, 1), (95, 1)]).unwrap()),
+            StationStats::new("Ürümqi \"\\\n", -999, 999, -12_345_678_901, 40_000_000_000)
+                .with_sum_squares(9_000_000_000_000_000),
+        ])
+        .with_skipped_lines(skipped_lines);
+        let mut sketch = QuantileSketch::new(8);
+        for value in 0..100 {
+            sketch.update(value as f64 * 0.123);
+        }
+        let summary = summary.merge(Summary::from_stations([StationStats::new(
+            "Tokyo", 0, 122, 609, 100,
+        )
+        .with_sketch(sketch)]));
+        for encoding in [Encoding::Binary, Encoding::Json] {
+            let data = summary.encode(encoding);
+            assert_eq!(Summary::decode(&data).unwrap(), summary, "{encoding:?}");
+            assert!(
+                Summary::decode(&data[..data.len() - 1]).is_err(),
+                "{encoding:?}"
+            );
+        }
+    }
+}
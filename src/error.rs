@@ -0,0 +1,410 @@
+use std::fmt;
+
+/// The reason a line of the input could not be parsed.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseErrorReason {
+    /// The line does not contain a semicolon separating the name from the value.
+    MissingSemicolon,
+    /// The line starts with a semicolon.
+    EmptyName,
+    /// The station name is not valid UTF-8.
+    InvalidUtf8,
+    /// There is nothing after the semicolon.
+    EmptyValue,
+    /// The value is not a number of the form `-?\d\d?\.\d`, or `-?\d\d?(\.\d+)?` if any precision is allowed.
+    InvalidValue,
+    /// The value has more than two digits before the period.
+    ValueOutOfRange,
+    /// The value has no period or no digit after the period.
+    MissingDecimal,
+    /// The value has more than a single digit after the period.
+    TooManyDecimals,
+}
+
+impl ParseErrorReason {
+    pub const ALL: [Self; 8] = [
+        Self::MissingSemicolon,
+        Self::EmptyName,
+        Self::InvalidUtf8,
+        Self::EmptyValue,
+        Self::InvalidValue,
+        Self::ValueOutOfRange,
+        Self::MissingDecimal,
+        Self::TooManyDecimals,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for ParseErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::MissingSemicolon => "missing semicolon",
+            Self::EmptyName => "empty station name",
+            Self::InvalidUtf8 => "station name is not valid UTF-8",
+            Self::EmptyValue => "empty value",
+            Self::InvalidValue => "invalid value",
+            Self::ValueOutOfRange => "value out of range",
+            Self::MissingDecimal => "missing decimal",
+            Self::TooManyDecimals => "too many decimals",
+        })
+    }
+}
+
+/// An error in the input, pointing to the byte at which the problem was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    offset: usize,
+    line: usize,
+    reason: ParseErrorReason,
+}
+
+impl ParseError {
+    /// The offset in bytes from the start of the input.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The line number, starting from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn reason(&self) -> ParseErrorReason {
+        self.reason
+    }
+
+    /// Moves an error found in a slice to its position in the full input,
+    /// given everything in the input preceding the slice.
+    pub(crate) fn offset_by(self, preceding: &[u8]) -> Self {
+        let lines = preceding.iter().filter(|&&c| c == b'\n').count();
+        self.offset_by_position(preceding.len(), lines)
+    }
+
+    /// Moves an error found in a part of the input to its position in the full input,
+    /// given the number of bytes and lines preceding the part.
+    pub(crate) fn offset_by_position(self, offset: usize, lines: usize) -> Self {
+        Self {
+            offset: self.offset + offset,
+            line: self.line + lines,
+            reason: self.reason,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid input on line {} at byte {}: {}.",
+            self.line, self.offset, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// How to handle invalid lines in the input.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseMode {
+    /// Stop at the first invalid line with a `ParseError`.
+    #[default]
+    Strict,
+    /// Skip invalid lines, counting them in `SkippedLines`.
+    Lenient,
+}
+
+/// The number of invalid lines skipped in lenient mode for each reason.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SkippedLines {
+    counts: [u64; ParseErrorReason::ALL.len()],
+}
+
+impl SkippedLines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&mut self, reason: ParseErrorReason) {
+        self.add(reason, 1);
+    }
+
+    pub(crate) fn add(&mut self, reason: ParseErrorReason, count: u64) {
+        self.counts[reason.index()] += count;
+    }
+
+    /// The number of lines skipped for the given reason.
+    pub fn get(&self, reason: ParseErrorReason) -> u64 {
+        self.counts[reason.index()]
+    }
+
+    /// The total number of lines skipped.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The reasons lines were skipped for along with the number of lines, leaving out reasons without any lines.
+    pub fn iter(&self) -> impl Iterator<Item = (ParseErrorReason, u64)> + '_ {
+        ParseErrorReason::ALL
+            .into_iter()
+            .map(|reason| (reason, self.get(reason)))
+            .filter(|&(_, count)| count > 0)
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts) {
+            *count += other_count;
+        }
+        self
+    }
+}
+
+impl fmt::Display for SkippedLines {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Skipped {} invalid lines", self.total())?;
+        let mut reasons = self.iter();
+        if let Some((reason, count)) = reasons.next() {
+            write!(f, " ({reason}: {count}")?;
+            for (reason, count) in reasons {
+                write!(f, ", {reason}: {count}")?;
+            }
+            f.write_str(")")?;
+        }
+        f.write_str(".")
+    }
+}
+
+/// The number of decimals allowed in values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decimals {
+    /// Exactly one decimal, as in the 1BRC input.
+    One,
+    /// Any number of decimals, including none, in which case the period is left out.
+    Any,
+}
+
+/// Check that a single line (without its newline) is of the form `<name>;<value>`.
+/// On failure, returns the offset into the line at which the problem was found along with the reason.
+/// This is slow compared to the parsers in the different versions,
+/// so they should only use it once they have detected a problem.
+pub(crate) fn validate_line(line: &[u8]) -> Result<(), (usize, ParseErrorReason)> {
+    validate_line_with(line, Decimals::One)
+}
+
+/// Like `validate_line`, but with the given number of decimals allowed in the value.
+pub(crate) fn validate_line_with(
+    line: &[u8],
+    decimals: Decimals,
+) -> Result<(), (usize, ParseErrorReason)> {
+    let Some(semicolon) = line.iter().position(|&c| c == b';') else {
+        return Err((line.len(), ParseErrorReason::MissingSemicolon));
+    };
+    let name = &line[..semicolon];
+    if name.is_empty() {
+        return Err((0, ParseErrorReason::EmptyName));
+    }
+    if let Err(error) = std::str::from_utf8(name) {
+        return Err((error.valid_up_to(), ParseErrorReason::InvalidUtf8));
+    }
+
+    let value_start = semicolon + 1;
+    let value = &line[value_start..];
+    if value.is_empty() {
+        return Err((value_start, ParseErrorReason::EmptyValue));
+    }
+    let mut index = usize::from(value[0] == b'-');
+    let integer_start = index;
+    while value.get(index).is_some_and(u8::is_ascii_digit) {
+        index += 1;
+    }
+    match index - integer_start {
+        0 => return Err((value_start + index, ParseErrorReason::InvalidValue)),
+        1 | 2 => {}
+        _ => return Err((value_start, ParseErrorReason::ValueOutOfRange)),
+    }
+    if decimals == Decimals::Any {
+        return match value.get(index) {
+            None => Ok(()),
+            Some(b'.') => {
+                let decimals_start = index + 1;
+                index = decimals_start;
+                while value.get(index).is_some_and(u8::is_ascii_digit) {
+                    index += 1;
+                }
+                if index == decimals_start {
+                    Err((value_start + index, ParseErrorReason::MissingDecimal))
+                } else if index < value.len() {
+                    Err((value_start + index, ParseErrorReason::InvalidValue))
+                } else {
+                    Ok(())
+                }
+            }
+            Some(_) => Err((value_start + index, ParseErrorReason::InvalidValue)),
+        };
+    }
+    match value.get(index) {
+        Some(b'.') => index += 1,
+        Some(_) => return Err((value_start + index, ParseErrorReason::InvalidValue)),
+        None => return Err((value_start + index, ParseErrorReason::MissingDecimal)),
+    }
+    match value.get(index) {
+        Some(c) if c.is_ascii_digit() => index += 1,
+        Some(_) => return Err((value_start + index, ParseErrorReason::InvalidValue)),
+        None => return Err((value_start + index, ParseErrorReason::MissingDecimal)),
+    }
+    match value.get(index) {
+        None => Ok(()),
+        Some(c) if c.is_ascii_digit() || *c == b'.' => {
+            Err((value_start + index, ParseErrorReason::TooManyDecimals))
+        }
+        Some(_) => Err((value_start + index, ParseErrorReason::InvalidValue)),
+    }
+}
+
+/// Find the end of the line starting at `line_start`, which is either a newline or the end of the slice.
+fn line_end(slice: &[u8], line_start: usize) -> usize {
+    slice[line_start..]
+        .iter()
+        .position(|&c| c == b'\n')
+        .map_or(slice.len(), |length| line_start + length)
+}
+
+/// Create the error for the invalid line starting at `line_start` in the slice.
+/// Should only be called when the line is known to be invalid.
+#[cold]
+#[inline(never)]
+pub(crate) fn line_error(slice: &[u8], line_start: usize) -> ParseError {
+    line_error_with(slice, line_start, Decimals::One)
+}
+
+/// Like `line_error`, but with the given number of decimals allowed in the value.
+#[cold]
+#[inline(never)]
+pub(crate) fn line_error_with(slice: &[u8], line_start: usize, decimals: Decimals) -> ParseError {
+    let line_end = line_end(slice, line_start);
+    let (offset, reason) = validate_line_with(&slice[line_start..line_end], decimals)
+        .expect_err("Only invalid lines should be reported.");
+    ParseError {
+        offset: line_start + offset,
+        line: slice[..line_start].iter().filter(|&&c| c == b'\n').count() + 1,
+        reason,
+    }
+}
+
+/// Find the reason the line starting at `line_start` in the slice is invalid along with the start of the next line.
+/// Should only be called when the line is known to be invalid.
+#[cold]
+#[inline(never)]
+pub(crate) fn skip_line(slice: &[u8], line_start: usize) -> (ParseErrorReason, usize) {
+    let line_end = line_end(slice, line_start);
+    let (_, reason) = validate_line(&slice[line_start..line_end])
+        .expect_err("Only invalid lines should be skipped.");
+    (reason, line_end + 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn valid_lines() {
+        for line in ["a;0.0", "Hamburg;12.0", "St. John's;-99.9", "Ürümqi;-1.5"] {
+            assert_eq!(validate_line(line.as_bytes()), Ok(()), "{line}");
+        }
+    }
+
+    #[test]
+    fn invalid_lines() {
+        use ParseErrorReason::*;
+        let cases: [(&[u8], usize, ParseErrorReason); 12] = [
+            (b"Hamburg", 7, MissingSemicolon),
+            (b";12.0", 0, EmptyName),
+            (b"Ham\xffburg;12.0", 3, InvalidUtf8),
+            (b"Hamburg;", 8, EmptyValue),
+            (b"Hamburg;NaN", 8, InvalidValue),
+            (b"Hamburg;-", 9, InvalidValue),
+            (b"Hamburg;123.4", 8, ValueOutOfRange),
+            (b"Hamburg;12", 10, MissingDecimal),
+            (b"Hamburg;12.", 11, MissingDecimal),
+            (b"Hamburg;12.34", 12, TooManyDecimals),
+            (b"Hamburg;1.2.3", 11, TooManyDecimals),
+            (b"Hamburg;1.2;", 11, InvalidValue),
+        ];
+        for (line, offset, reason) in cases {
+            assert_eq!(validate_line(line), Err((offset, reason)), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn any_decimals() {
+        use ParseErrorReason::*;
+        for line in [
+            "a;0",
+            "Hamburg;12.0",
+            "St. John's;-99.987",
+            "Ürümqi;-1.50000",
+        ] {
+            assert_eq!(
+                validate_line_with(line.as_bytes(), Decimals::Any),
+                Ok(()),
+                "{line}"
+            );
+        }
+        let cases: [(&[u8], usize, ParseErrorReason); 5] = [
+            (b"Hamburg;", 8, EmptyValue),
+            (b"Hamburg;123.4", 8, ValueOutOfRange),
+            (b"Hamburg;12.", 11, MissingDecimal),
+            (b"Hamburg;1.2.3", 11, InvalidValue),
+            (b"Hamburg;1e5", 9, InvalidValue),
+        ];
+        for (line, offset, reason) in cases {
+            assert_eq!(
+                validate_line_with(line, Decimals::Any),
+                Err((offset, reason)),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn error_position() {
+        let slice = b"Hamburg;12.0\nBulawayo;8.9\nPalembang;38.8.\nHamburg;34.2";
+        let error = line_error(slice, 26);
+        assert_eq!(error.line(), 3);
+        assert_eq!(error.offset(), 40);
+        assert_eq!(error.reason(), ParseErrorReason::TooManyDecimals);
+
+        let error = error.offset_by(b"St. John's;15.2\n");
+        assert_eq!(error.line(), 4);
+        assert_eq!(error.offset(), 56);
+    }
+
+    #[test]
+    fn skip() {
+        let slice = b"Hamburg;12.0\nBulawayo\nPalembang;38.8";
+        assert_eq!(
+            skip_line(slice, 13),
+            (ParseErrorReason::MissingSemicolon, 22)
+        );
+        assert_eq!(
+            skip_line(b"Hamburg;12.0\nBulawayo;", 13),
+            (ParseErrorReason::EmptyValue, 23)
+        );
+    }
+}
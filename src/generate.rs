@@ -0,0 +1,433 @@
+use std::{collections::HashSet, io::Write};
+
+use anyhow::{ensure, Result};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    aggregator::{Aggregator, Stats},
+    histogram::{MAX_TENTHS, MIN_TENTHS},
+    Summary,
+};
+
+/// The fewest rows generated as one unit of work, each from its own random state.
+const MIN_CHUNK_ROWS: u64 = 1 << 16;
+/// The standard deviation of the values of a station around its mean in tenths of a degree.
+const STD_DEV_TENTHS: f64 = 100.;
+/// The longest station name allowed in bytes.
+const MAX_NAME_LEN: usize = 100;
+/// The difference between the two seeds of `rustc_hash::FxHasher`, which `Profile::Collisions` relies on.
+const FX_SEED_DIFFERENCE: u64 = 0x243f_6a88_85a3_08d3 ^ 0x1319_8a2e_0370_7344;
+/// Parts that station names are made of, including some multi-byte characters.
+const SYLLABLES: &[&str] = &[
+    "ba", "bel", "burg", "da", "dor", "el", "fa", "gen", "ha", "ka", "la", "lo", "ma", "mi", "na",
+    "no", "pol", "ra", "ri", "sa", "su", "ta", "te", "ton", "vi", "vin", "za", "é", "ø", "ü", "ño",
+    "ši", "ść", "ă",
+];
+
+/// A small, fast random number generator (SplitMix64), so the data only depends on the seed.
+#[derive(Debug, Clone)]
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A uniformly distributed number below `n`.
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next() as u128 * n as u128) >> 64) as u64
+    }
+
+    /// A uniformly distributed number in `[0, 1)`.
+    fn uniform(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// An approximately normally distributed number with mean 0 and standard deviation 1.
+    /// This sums uniform numbers instead of using `ln` and `cos`, whose results can differ between platforms.
+    fn normal(&mut self) -> f64 {
+        (0..12).map(|_| self.uniform()).sum::<f64>() - 6.
+    }
+}
+
+/// The kinds of data `Generator` can make, from realistic data to the worst cases the rules of the challenge allow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Profile {
+    /// Names of one or two words and values spread around a mean temperature for each station.
+    #[default]
+    Realistic,
+    /// Names of exactly 100 bytes, the longest allowed.
+    LongNames,
+    /// Names of 100 bytes that only differ in their last few bytes.
+    SamePrefix,
+    /// Names of a single byte, which allows at most 94 stations.
+    ShortNames,
+    /// Only the values -99.9 and 99.9.
+    Extremes,
+    /// Stations picked with a Zipf distribution, so the first few stations get most of the rows.
+    Zipf,
+    /// Pairs of names of 16 bytes with the same FxHash, which the versions since `v1` hash names with.
+    Collisions,
+}
+
+impl Profile {
+    /// The number of stations used when none is given.
+    pub fn default_stations(self) -> usize {
+        match self {
+            Self::Realistic | Self::Extremes => 413,
+            Self::ShortNames => short_names().len(),
+            Self::LongNames | Self::SamePrefix | Self::Zipf | Self::Collisions => 10_000,
+        }
+    }
+}
+
+/// Generates random measurements like those of the challenge, along with their exact summary.
+///
+/// Every station gets a name and a mean temperature, and its values are spread around that mean
+/// and rounded to tenths of a degree. The rows pick a station uniformly at random.
+/// Other profiles change the names, the values or how stations are picked.
+/// The data only depends on the profile, the seed, the number of stations and the number of rows,
+/// no matter how many threads generate it.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    profile: Profile,
+    seed: u64,
+    names: Vec<String>,
+    means: Vec<i32>,
+    /// The sum of the weights of the stations up to each station for `Profile::Zipf`.
+    cumulative_weights: Option<Vec<f64>>,
+}
+
+impl Generator {
+    /// Creates a generator for the given number of stations.
+    /// Fails if there are no stations or more than the profile has names for.
+    pub fn new(profile: Profile, num_stations: usize, seed: u64) -> Result<Self> {
+        ensure!(num_stations > 0, "There should be at least one station.");
+        let mut rng = Rng::new(seed);
+        let names = match profile {
+            Profile::Realistic | Profile::Extremes | Profile::Zipf => {
+                unique_names(num_stations, &mut rng, station_name)
+            }
+            Profile::LongNames => unique_names(num_stations, &mut rng, long_name),
+            Profile::SamePrefix => {
+                let width = (num_stations - 1).to_string().len();
+                let mut prefix = long_name(&mut rng);
+                resize(&mut prefix, MAX_NAME_LEN - width, &mut rng);
+                (0..num_stations)
+                    .map(|index| format!("{prefix}{index:0width$}"))
+                    .collect()
+            }
+            Profile::ShortNames => {
+                let names = short_names();
+                ensure!(
+                    num_stations <= names.len(),
+                    "There are only {} names of a single byte.",
+                    names.len()
+                );
+                names[..num_stations].to_vec()
+            }
+            Profile::Collisions => colliding_names(num_stations, &mut rng),
+        };
+        let means = (0..num_stations)
+            .map(|_| rng.below(700) as i32 - 300)
+            .collect();
+        let cumulative_weights = (profile == Profile::Zipf).then(|| {
+            (1..=num_stations)
+                .scan(0., |total, rank| {
+                    *total += 1. / rank as f64;
+                    Some(*total)
+                })
+                .collect()
+        });
+        Ok(Self {
+            profile,
+            seed,
+            names,
+            means,
+            cumulative_weights,
+        })
+    }
+
+    /// The names of the stations.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Generates the given number of rows in parallel and writes them to the writer,
+    /// returning the exact summary of them.
+    pub fn write(&self, rows: u64, mut writer: impl Write) -> Result<Summary> {
+        // Enough rows per chunk that merging the statistics of a chunk costs little compared to generating it.
+        let chunk_rows = MIN_CHUNK_ROWS.max(self.names.len() as u64);
+        let num_chunks = rows.div_ceil(chunk_rows);
+        // Chunks are generated in batches, so only a batch has to be kept in memory before it is written.
+        let batch_size = 2 * rayon::current_num_threads() as u64;
+        let mut stats = vec![Stats::default(); self.names.len()];
+        for batch_start in (0..num_chunks).step_by(batch_size as usize) {
+            let batch_end = num_chunks.min(batch_start + batch_size);
+            let chunks: Vec<(Vec<u8>, Vec<Stats>)> = (batch_start..batch_end)
+                .into_par_iter()
+                .map(|chunk| {
+                    let start = chunk * chunk_rows;
+                    self.chunk(chunk, (rows - start).min(chunk_rows))
+                })
+                .collect();
+            for (data, chunk_stats) in chunks {
+                writer.write_all(&data)?;
+                for (stats, chunk_stats) in stats.iter_mut().zip(&chunk_stats) {
+                    stats.merge(chunk_stats);
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(Summary::from_stations(
+            self.names
+                .iter()
+                .zip(stats)
+                .filter(|(_, stats)| *stats != Stats::default())
+                .map(|(name, stats)| stats.into_station_stats(name.as_str())),
+        ))
+    }
+
+    /// Generates the rows of a chunk along with the statistics of each station in it.
+    fn chunk(&self, index: u64, rows: u64) -> (Vec<u8>, Vec<Stats>) {
+        // Each chunk has its own random state, so chunks can be generated in any order.
+        let mut rng = Rng::new(self.seed ^ Rng::new(index).next());
+        let mut data = Vec::with_capacity(rows as usize * 16);
+        let mut stats = vec![Stats::default(); self.names.len()];
+        for _ in 0..rows {
+            let station = match &self.cumulative_weights {
+                Some(cumulative_weights) => {
+                    let weight = rng.uniform() * cumulative_weights.last().unwrap();
+                    cumulative_weights.partition_point(|&total| total <= weight)
+                }
+                None => rng.below(self.names.len() as u64) as usize,
+            };
+            let value = if self.profile == Profile::Extremes {
+                [MIN_TENTHS, MAX_TENTHS][rng.below(2) as usize]
+            } else {
+                let noise = (rng.normal() * STD_DEV_TENTHS).round() as i32;
+                (self.means[station] + noise).clamp(MIN_TENTHS, MAX_TENTHS)
+            };
+            stats[station].update(value);
+            data.extend_from_slice(self.names[station].as_bytes());
+            data.push(b';');
+            push_tenths(&mut data, value);
+            data.push(b'\n');
+        }
+        (data, stats)
+    }
+}
+
+/// The given number of distinct names made by the given function.
+fn unique_names(num_stations: usize, rng: &mut Rng, name: fn(&mut Rng) -> String) -> Vec<String> {
+    let mut seen = HashSet::with_capacity(num_stations);
+    let mut names = Vec::with_capacity(num_stations);
+    while names.len() < num_stations {
+        let name = name(rng);
+        if seen.insert(name.clone()) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Every name of a single printable ASCII character.
+fn short_names() -> Vec<String> {
+    (b' '..=b'~')
+        .filter(|&c| c != b';')
+        .map(|c| char::from(c).to_string())
+        .collect()
+}
+
+/// Make the name exactly the given number of bytes by removing characters from the end.
+/// Removing a multi-byte character may leave a gap, which is filled with ASCII letters.
+fn resize(name: &mut String, len: usize, rng: &mut Rng) {
+    while name.len() > len {
+        name.pop();
+    }
+    while name.len() < len {
+        name.push(char::from(b'a' + rng.below(26) as u8));
+    }
+}
+
+/// A random name of exactly 100 bytes.
+fn long_name(rng: &mut Rng) -> String {
+    let mut name = word(rng);
+    while name.len() < MAX_NAME_LEN {
+        name.push(' ');
+        name.push_str(&word(rng));
+    }
+    resize(&mut name, MAX_NAME_LEN, rng);
+    name
+}
+
+/// Pairs of distinct names of 16 bytes with the same FxHash, and a normal name at the end if the number is odd.
+///
+/// FxHash mixes the halves `a` and `b` of a 16-byte name as `(a ^ SEED1) * (b ^ SEED2)`,
+/// which is the same for the halves `b ^ SEED1 ^ SEED2` and `a ^ SEED1 ^ SEED2`.
+/// Halves are searched for that are valid names both as they are and with the seeds XOR'ed in,
+/// and every pair of halves found gives a pair of colliding names.
+fn colliding_names(num_stations: usize, rng: &mut Rng) -> Vec<String> {
+    let is_name = |half: &[u8]| {
+        std::str::from_utf8(half).is_ok() && !half.iter().any(|&c| c == b';' || c == b'\n')
+    };
+    let mut halves: Vec<u64> = vec![];
+    let mut seen = HashSet::with_capacity(num_stations);
+    let mut names = Vec::with_capacity(num_stations);
+    while names.len() + 1 < num_stations {
+        let half = loop {
+            let bytes: [u8; 8] = std::array::from_fn(|_| 0x20 + rng.below(0xf5 - 0x20) as u8);
+            let other = (u64::from_le_bytes(bytes) ^ FX_SEED_DIFFERENCE).to_le_bytes();
+            if is_name(&bytes) && is_name(&other) {
+                break u64::from_le_bytes(bytes);
+            }
+        };
+        halves.push(half);
+        for &other_half in &halves {
+            let pair = [
+                [half, other_half],
+                [other_half ^ FX_SEED_DIFFERENCE, half ^ FX_SEED_DIFFERENCE],
+            ]
+            .map(|halves| {
+                let bytes = [halves[0].to_le_bytes(), halves[1].to_le_bytes()].concat();
+                String::from_utf8(bytes).unwrap()
+            });
+            if names.len() + 1 < num_stations
+                && pair[0] != pair[1]
+                && pair.iter().all(|name| !seen.contains(name))
+            {
+                seen.extend(pair.clone());
+                names.extend(pair);
+            }
+        }
+    }
+    if names.len() < num_stations {
+        names.push(station_name(rng));
+    }
+    names
+}
+
+/// A random station name of one or two capitalized words.
+fn station_name(rng: &mut Rng) -> String {
+    let mut name = word(rng);
+    if rng.below(4) == 0 {
+        name.push(' ');
+        name.push_str(&word(rng));
+    }
+    name
+}
+
+/// A random capitalized word of up to four syllables.
+fn word(rng: &mut Rng) -> String {
+    let num_syllables = 1 + rng.below(4);
+    let word: String = (0..num_syllables)
+        .map(|_| SYLLABLES[rng.below(SYLLABLES.len() as u64) as usize])
+        .collect();
+    let mut chars = word.chars();
+    let first = chars.next().unwrap();
+    first.to_uppercase().chain(chars).collect()
+}
+
+/// Writes a value in tenths of a degree with exactly one decimal.
+fn push_tenths(data: &mut Vec<u8>, value: i32) {
+    if value < 0 {
+        data.push(b'-');
+    }
+    let value = value.unsigned_abs();
+    if value >= 100 {
+        data.push(b'0' + (value / 100) as u8);
+    }
+    data.extend_from_slice(&[
+        b'0' + (value / 10 % 10) as u8,
+        b'.',
+        b'0' + (value % 10) as u8,
+    ]);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let generator = Generator::new(Profile::Realistic, 50, 7).unwrap();
+        assert_eq!(generator.names().len(), 50);
+        let rows = MIN_CHUNK_ROWS + 1000;
+        let mut data = vec![];
+        let summary = generator.write(rows, &mut data).unwrap();
+        assert_eq!(
+            summary.iter().map(|station| station.count()).sum::<u64>(),
+            rows
+        );
+        assert_eq!(
+            crate::v3::summarize_bytes(&data, None, 4, crate::ParseMode::Strict).unwrap(),
+            summary
+        );
+
+        let mut again = vec![];
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            let generator = Generator::new(Profile::Realistic, 50, 7).unwrap();
+            generator.write(rows, &mut again)
+        })
+        .unwrap();
+        assert_eq!(data, again);
+        let mut other = vec![];
+        Generator::new(Profile::Realistic, 50, 8)
+            .unwrap()
+            .write(rows, &mut other)
+            .unwrap();
+        assert_ne!(data, other);
+    }
+
+    #[test]
+    fn names() {
+        // Multi-byte characters end up on the boundary of the prefix with some seeds, like 7.
+        for seed in 0..20 {
+            for profile in [Profile::LongNames, Profile::SamePrefix] {
+                let generator = Generator::new(profile, 1000, seed).unwrap();
+                assert!(
+                    generator
+                        .names()
+                        .iter()
+                        .all(|name| name.len() == MAX_NAME_LEN),
+                    "{profile:?} with seed {seed}."
+                );
+            }
+        }
+        let generator = Generator::new(Profile::SamePrefix, 1000, 1).unwrap();
+        assert!(generator.names()[999].ends_with("999"));
+        assert!(Generator::new(Profile::ShortNames, 95, 1).is_err());
+
+        let generator = Generator::new(Profile::Collisions, 101, 1).unwrap();
+        let names = generator.names();
+        assert_eq!(names.iter().collect::<HashSet<_>>().len(), 101);
+        for pair in names[..100].chunks(2) {
+            let hash = |name: &String| crate::common::hash_str(name.as_bytes());
+            assert_eq!(hash(&pair[0]), hash(&pair[1]), "{pair:?}");
+        }
+    }
+
+    #[test]
+    fn tenths() {
+        let mut data = vec![];
+        for value in [-999, -100, -5, 0, 7, 42, 999] {
+            push_tenths(&mut data, value);
+            data.push(b' ');
+        }
+        assert_eq!(data, b"-99.9 -10.0 -0.5 0.0 0.7 4.2 99.9 ");
+    }
+}
@@ -0,0 +1,142 @@
+/// The smallest value a measurement can have, in tenths of a degree.
+pub const MIN_TENTHS: i32 = -999;
+/// The largest value a measurement can have, in tenths of a degree.
+pub const MAX_TENTHS: i32 = 999;
+/// The number of distinct values a measurement can have.
+pub const BUCKETS: usize = (MAX_TENTHS - MIN_TENTHS + 1) as usize;
+
+/// The exact distribution of the measurements of a station.
+/// Since measurements have a single decimal and are between -99.9 and 99.9,
+/// there are only 1999 possible values, so counting each of them is feasible.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    /// The number of measurements of each value present in tenths of a degree, sorted by value.
+    bins: Vec<(i32, u64)>,
+}
+
+impl Histogram {
+    /// Creates a histogram from a count for every possible value, starting at `MIN_TENTHS`.
+    pub fn from_buckets(buckets: &[u32; BUCKETS]) -> Self {
+        Self {
+            bins: (MIN_TENTHS..)
+                .zip(buckets)
+                .filter(|&(_, &count)| count > 0)
+                .map(|(value, &count)| (value, count as u64))
+                .collect(),
+        }
+    }
+
+    /// Creates a histogram from the number of measurements of each value in tenths of a degree.
+    /// Returns `None` if a value is out of range or repeated, or if the values are not sorted.
+    pub fn from_bins(bins: Vec<(i32, u64)>) -> Option<Self> {
+        let in_range = bins
+            .iter()
+            .all(|&(value, count)| (MIN_TENTHS..=MAX_TENTHS).contains(&value) && count > 0);
+        let sorted = bins.windows(2).all(|pair| pair[0].0 < pair[1].0);
+        (in_range && sorted).then_some(Self { bins })
+    }
+
+    /// The number of measurements of each value present in tenths of a degree, sorted by value.
+    pub fn bins(&self) -> &[(i32, u64)] {
+        &self.bins
+    }
+
+    /// The total number of measurements.
+    pub fn count(&self) -> u64 {
+        self.bins.iter().map(|&(_, count)| count).sum()
+    }
+
+    /// The sum of the squares of the measurements in tenths of a degree.
+    pub fn sum_squares_tenths(&self) -> u64 {
+        self.bins
+            .iter()
+            .map(|&(value, count)| (value * value) as u64 * count)
+            .sum()
+    }
+
+    /// Combines the histograms of two disjoint sets of measurements.
+    pub fn merge(&mut self, other: &Self) {
+        let mut bins = Vec::with_capacity(self.bins.len().max(other.bins.len()));
+        let mut a_iter = self.bins.iter().peekable();
+        let mut b_iter = other.bins.iter().peekable();
+        while let (Some(&&(a_value, a_count)), Some(&&(b_value, b_count))) =
+            (a_iter.peek(), b_iter.peek())
+        {
+            if a_value < b_value {
+                bins.push((a_value, a_count));
+                a_iter.next();
+            } else if a_value > b_value {
+                bins.push((b_value, b_count));
+                b_iter.next();
+            } else {
+                bins.push((a_value, a_count + b_count));
+                a_iter.next();
+                b_iter.next();
+            }
+        }
+        bins.extend(a_iter.chain(b_iter));
+        self.bins = bins;
+    }
+
+    /// The exact `q`-quantile in tenths of a degree using the nearest-rank method,
+    /// i.e. the smallest value such that at least a fraction `q` of the measurements are at most that value.
+    ///
+    /// Panics if `q` is not between 0 and 1 or the histogram is empty.
+    pub fn quantile_tenths(&self, q: f64) -> i32 {
+        assert!(
+            (0. ..=1.).contains(&q),
+            "The quantile should be between 0 and 1."
+        );
+        let count = self.count();
+        assert!(count > 0, "An empty histogram has no quantiles.");
+        let rank = ((q * count as f64).ceil() as u64).clamp(1, count);
+        let mut seen = 0;
+        for &(value, bin_count) in &self.bins {
+            seen += bin_count;
+            if seen >= rank {
+                return value;
+            }
+        }
+        unreachable!("The rank is at most the number of measurements.")
+    }
+
+    /// The exact median in tenths of a degree. For an even number of measurements this is the lower of the two middle values.
+    pub fn median_tenths(&self) -> i32 {
+        self.quantile_tenths(0.5)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quantiles() {
+        let mut buckets = [0; BUCKETS];
+        // The values -99.9, 0.0 (twice), 1.5 and 99.9.
+        buckets[0] = 1;
+        buckets[999] = 2;
+        buckets[1014] = 1;
+        buckets[BUCKETS - 1] = 1;
+        let histogram = Histogram::from_buckets(&buckets);
+        assert_eq!(histogram.bins(), [(-999, 1), (0, 2), (15, 1), (999, 1)]);
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.median_tenths(), 0);
+        assert_eq!(histogram.quantile_tenths(0.), -999);
+        assert_eq!(histogram.quantile_tenths(0.2), -999);
+        assert_eq!(histogram.quantile_tenths(0.21), 0);
+        assert_eq!(histogram.quantile_tenths(0.8), 15);
+        assert_eq!(histogram.quantile_tenths(0.95), 999);
+        assert_eq!(histogram.quantile_tenths(1.), 999);
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = Histogram::from_bins(vec![(-5, 1), (3, 2)]).unwrap();
+        let b = Histogram::from_bins(vec![(-7, 1), (3, 1), (10, 4)]).unwrap();
+        a.merge(&b);
+        assert_eq!(a.bins(), [(-7, 1), (-5, 1), (3, 3), (10, 4)]);
+        assert!(Histogram::from_bins(vec![(3, 1), (-5, 1)]).is_none());
+        assert!(Histogram::from_bins(vec![(1000, 1)]).is_none());
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{ensure, Context, Result};
+use flate2::read::{GzDecoder, MultiGzDecoder};
+use memmap::MmapOptions;
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
+use xz2::read::XzDecoder;
+
+use crate::{
+    stream::{summarize_reader, DEFAULT_BUFFER_SIZE},
+    ParseMode, SummarizeBytesFn, Summary,
+};
+
+/// Frames larger than this are not decompressed in parallel, since each frame is kept in memory.
+const MAX_PARALLEL_FRAME_SIZE: u64 = 1 << 26;
+/// The number of compressed bytes decompressed in parallel at a time.
+const FRAME_BATCH_SIZE: usize = 1 << 22;
+
+/// The compression formats recognized in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// Detect the compression format from the magic bytes at the start of the input.
+    pub fn detect(header: &[u8]) -> Self {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Self::Gzip
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Self::Zstd
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Self::Xz
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// Summarize the file at the given path, decompressing it first if it is compressed.
+/// Uncompressed files are memory mapped exactly like in the `summarize` function of each version.
+/// Zstd files made of many small frames and BGZF files are decompressed in parallel.
+pub fn summarize_file(
+    path: &Path,
+    summarize_bytes: SummarizeBytesFn,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<Summary> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        // Only regular files can be memory mapped, so anything else, like a named pipe, is streamed.
+        return summarize_stream(file, summarize_bytes, num_slices, mode);
+    }
+    if metadata.len() == 0 {
+        // Empty files cannot be mapped.
+        return summarize_bytes(&[], None, num_slices, mode);
+    }
+    let data = unsafe { MmapOptions::new().map(&file)? };
+
+    let stream = |reader: Box<dyn Read + Send + '_>| {
+        summarize_reader(
+            reader,
+            summarize_bytes,
+            num_slices,
+            mode,
+            DEFAULT_BUFFER_SIZE,
+        )
+    };
+    match Compression::detect(&data) {
+        Compression::None => summarize_bytes(&data, None, num_slices, mode),
+        Compression::Gzip => match bgzf_blocks(&data) {
+            Some(blocks) => stream(Box::new(ParallelFrames::new(blocks, decompress_gzip))),
+            None => stream(Box::new(MultiGzDecoder::new(&data[..]))),
+        },
+        Compression::Zstd => match zstd_frames(&data) {
+            Some(frames) => stream(Box::new(ParallelFrames::new(frames, decompress_zstd))),
+            None => stream(Box::new(zstd::Decoder::with_buffer(&data[..])?)),
+        },
+        Compression::Xz => stream(Box::new(XzDecoder::new_multi_decoder(&data[..]))),
+    }
+}
+
+/// Summarize a number of files in parallel, returning the summary of each file in the given order.
+/// The slices are divided between the files by size, giving each file at least one.
+/// The summaries can be combined with `Summary::merge`.
+pub fn summarize_files(
+    paths: &[PathBuf],
+    summarize_bytes: SummarizeBytesFn,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<Vec<Summary>> {
+    ensure!(num_slices > 0, "There should be at least one slice.");
+    let sizes = paths
+        .iter()
+        .map(|path| {
+            let metadata =
+                std::fs::metadata(path).with_context(|| format!("Failed to open {path:?}."))?;
+            Ok(metadata.len())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let total_size = sizes.iter().sum::<u64>().max(1);
+    paths
+        .par_iter()
+        .zip(sizes)
+        .map(|(path, size)| {
+            let file_slices = (num_slices as u64 * size).div_ceil(total_size).max(1) as usize;
+            summarize_file(path, summarize_bytes, file_slices, mode)
+                .with_context(|| format!("Failed to summarize {path:?}."))
+        })
+        .collect()
+}
+
+/// Expand glob patterns into the paths of the files they match, sorted by path.
+/// Patterns without any special characters are kept as they are, even if the file does not exist.
+pub fn expand_globs(patterns: &[impl AsRef<str>]) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for pattern in patterns {
+        let pattern = pattern.as_ref();
+        if !pattern.contains(['*', '?', '[']) {
+            paths.push(PathBuf::from(pattern));
+            continue;
+        }
+        let matches = glob::glob(pattern)
+            .with_context(|| format!("Invalid pattern {pattern:?}."))?
+            .collect::<Result<Vec<_>, _>>()?;
+        ensure!(!matches.is_empty(), "No files match {pattern:?}.");
+        paths.extend(matches);
+    }
+    Ok(paths)
+}
+
+/// Summarize everything read from `reader`, decompressing it first if it is compressed.
+pub fn summarize_stream(
+    reader: impl Read + Send,
+    summarize_bytes: SummarizeBytesFn,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<Summary> {
+    let mut reader = BufReader::new(reader);
+    let compression = Compression::detect(reader.fill_buf()?);
+    let reader: Box<dyn Read + Send> = match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+        Compression::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+    };
+    summarize_reader(
+        reader,
+        summarize_bytes,
+        num_slices,
+        mode,
+        DEFAULT_BUFFER_SIZE,
+    )
+}
+
+/// Split gzip data into its BGZF blocks using the block sizes stored in the `BC` extra field of each member.
+/// Returns `None` if any member is not a BGZF block, in which case the boundaries cannot be found without decompressing.
+fn bgzf_blocks(data: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut blocks = vec![];
+    let mut rest = data;
+    while !rest.is_empty() {
+        // Magic bytes, the deflate method and the FEXTRA flag.
+        if rest.get(..4)? != [0x1f, 0x8b, 0x08, 0x04] {
+            return None;
+        }
+        let extra_len = u16::from_le_bytes(rest.get(10..12)?.try_into().unwrap()) as usize;
+        let mut extra = rest.get(12..12 + extra_len)?;
+        let mut block_size = None;
+        while let [subfield_1, subfield_2, len_1, len_2, ref tail @ ..] = *extra {
+            let len = u16::from_le_bytes([len_1, len_2]) as usize;
+            let data = tail.get(..len)?;
+            if (subfield_1, subfield_2, len) == (b'B', b'C', 2) {
+                block_size = Some(u16::from_le_bytes([data[0], data[1]]) as usize + 1);
+            }
+            extra = &tail[len..];
+        }
+        let block = rest.get(..block_size?)?;
+        blocks.push(block);
+        rest = &rest[block.len()..];
+    }
+    Some(blocks)
+}
+
+/// Split zstd data into its frames.
+/// Returns `None` if there is only a single frame or if any frame is too large to keep in memory,
+/// in which case there is nothing to gain from decompressing frames in parallel.
+fn zstd_frames(data: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut frames = vec![];
+    let mut rest = data;
+    while !rest.is_empty() {
+        let frame_len = zstd::zstd_safe::find_frame_compressed_size(rest).ok()?;
+        let frame = &rest[..frame_len];
+        let content_size = zstd::zstd_safe::get_frame_content_size(frame).ok()??;
+        if content_size > MAX_PARALLEL_FRAME_SIZE {
+            return None;
+        }
+        frames.push(frame);
+        rest = &rest[frame_len..];
+    }
+    (frames.len() > 1).then_some(frames)
+}
+
+fn decompress_gzip(block: &[u8]) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+    GzDecoder::new(block).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn decompress_zstd(frame: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::decode_all(frame)
+}
+
+/// Reads the decompressed contents of a number of independently compressed frames in order,
+/// decompressing a batch of frames in parallel whenever the previous batch has been read.
+struct ParallelFrames<'a> {
+    frames: VecDeque<&'a [u8]>,
+    decompress: fn(&[u8]) -> io::Result<Vec<u8>>,
+    decompressed: VecDeque<Vec<u8>>,
+    position: usize,
+}
+
+impl<'a> ParallelFrames<'a> {
+    fn new(frames: Vec<&'a [u8]>, decompress: fn(&[u8]) -> io::Result<Vec<u8>>) -> Self {
+        Self {
+            frames: frames.into(),
+            decompress,
+            decompressed: VecDeque::new(),
+            position: 0,
+        }
+    }
+
+    fn decompress_batch(&mut self) -> io::Result<()> {
+        let mut batch_len = 0;
+        let mut compressed_size = 0;
+        while compressed_size < FRAME_BATCH_SIZE && batch_len < self.frames.len() {
+            compressed_size += self.frames[batch_len].len();
+            batch_len += 1;
+        }
+        let batch: Vec<_> = self.frames.drain(..batch_len).collect();
+        let decompressed: Vec<_> = batch.into_par_iter().map(self.decompress).collect();
+        for frame in decompressed {
+            self.decompressed.push_back(frame?);
+        }
+        Ok(())
+    }
+}
+
+impl Read for ParallelFrames<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(frame) = self.decompressed.front() {
+                let rest = &frame[self.position..];
+                if !rest.is_empty() {
+                    let len = rest.len().min(buf.len());
+                    buf[..len].copy_from_slice(&rest[..len]);
+                    self.position += len;
+                    return Ok(len);
+                }
+                self.decompressed.pop_front();
+                self.position = 0;
+            } else if self.frames.is_empty() {
+                return Ok(0);
+            } else {
+                self.decompress_batch()?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        assert_eq!(Compression::detect(b"Hamburg;12.0"), Compression::None);
+        assert_eq!(Compression::detect(&[]), Compression::None);
+        assert_eq!(Compression::detect(&[0x1f, 0x8b, 0x08]), Compression::Gzip);
+        assert_eq!(
+            Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::detect(b"\xfd7zXZ\x00\x00"), Compression::Xz);
+    }
+}
@@ -1,19 +1,139 @@
+pub mod aggregator;
+pub mod bench;
 pub mod cli;
+mod common;
+pub mod encoding;
+pub mod error;
+pub mod generate;
+pub mod histogram;
+pub mod input;
+pub mod output;
+pub mod sketch;
+pub mod stream;
+pub mod summary;
+pub mod timing;
 
 use anyhow::Result;
+pub use error::{ParseError, ParseErrorReason, ParseMode, SkippedLines};
+pub use histogram::Histogram;
+pub use output::OutputFormat;
+pub use sketch::QuantileSketch;
+pub use summary::{StationStats, Summary};
 
-pub type SummarizeFn = fn(&std::path::Path, Option<usize>, usize) -> Result<String>;
+pub type SummarizeFn = fn(&std::path::Path, Option<usize>, usize, ParseMode) -> Result<Summary>;
+pub type SummarizeBytesFn = fn(&[u8], Option<usize>, usize, ParseMode) -> Result<Summary>;
 
-// Macro to create list of `summarize` functions found in modules
-macro_rules! summarize_functions {
-    ($($module:ident),*) => {
+/// What a version can do beyond computing the minimum, mean and maximum of each station.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Keeps the exact sum of squares of each station, giving the variance and standard deviation.
+    pub squares: bool,
+    /// Keeps the exact histogram of each station, giving exact quantiles.
+    pub histogram: bool,
+    /// Keeps a `QuantileSketch` of each station, giving approximate quantiles.
+    pub sketch: bool,
+    /// Accepts values with any number of decimals instead of exactly one.
+    pub any_decimals: bool,
+}
+
+impl Capabilities {
+    /// No capabilities, which the registry starts from.
+    const NONE: Self = Self {
+        squares: false,
+        histogram: false,
+        sketch: false,
+        any_decimals: false,
+    };
+}
+
+/// The names of the enabled capabilities separated by commas.
+impl std::fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = [
+            (self.squares, "squares"),
+            (self.histogram, "histogram"),
+            (self.sketch, "sketch"),
+            (self.any_decimals, "any_decimals"),
+        ];
+        let names: Vec<&str> = names
+            .into_iter()
+            .filter_map(|(enabled, name)| enabled.then_some(name))
+            .collect();
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+/// An implementation of the challenge in the registry given by `VERSIONS`.
+#[derive(Debug, Clone, Copy)]
+pub struct Version {
+    /// The name of the module implementing the version, which is also how it is selected on the command line.
+    pub name: &'static str,
+    /// The documentation of the module.
+    pub description: &'static str,
+    pub summarize: SummarizeFn,
+    pub summarize_bytes: SummarizeBytesFn,
+    pub capabilities: Capabilities,
+}
+
+/// The version used when none is given.
+pub const DEFAULT_VERSION: &str = "v3";
+
+/// Find the version with the given name.
+/// A bare number `n` is accepted for the numbered version `vn`.
+pub fn version(name: &str) -> Option<&'static Version> {
+    let name = match name.parse::<u32>() {
+        Ok(number) => format!("v{number}"),
+        Err(_) => name.to_string(),
+    };
+    VERSIONS.iter().find(|version| version.name == name)
+}
+
+// Macro to declare the modules of the versions along with the registry of them.
+// The documentation of each module doubles as its description and the listed capabilities are enabled.
+macro_rules! versions {
+    ($($(#[doc = $doc:literal])* $module:ident [$($capability:ident),*],)*) => {
         // Import modules
-        $(pub mod $module;)*
+        $(
+            $(#[doc = $doc])*
+            pub mod $module;
+        )*
 
-        pub fn versions() -> Vec<SummarizeFn> {
-            vec![$($module::summarize),*]
-        }
+        /// Every version, in the order they were written.
+        pub static VERSIONS: &[Version] = &[$(
+            Version {
+                name: stringify!($module),
+                description: concat!($($doc),*),
+                summarize: $module::summarize,
+                summarize_bytes: $module::summarize_bytes,
+                capabilities: Capabilities {
+                    $($capability: true,)*
+                    ..Capabilities::NONE
+                },
+            },
+        )*];
     };
 }
 
-summarize_functions!(v0, v1, v2, v3);
+versions! {
+    /// A basic implementation using `std` for parsing.
+    v0 [],
+    /// A custom parser finding the semicolon with bit manipulation.
+    v1 [],
+    /// Stores the statistics of each station directly in the `HashMap` of `v1`.
+    v2 [],
+    /// Hashes each name once and has the `HashMap` of `v2` use that hash directly.
+    v3 [],
+    /// `v3` with all `assert`s made into `debug_assert`s.
+    vno_assert [],
+    /// An attempt at parsing lines in batches on each thread.
+    vbatch [],
+    /// Exposes the parser of `v3`, which is generic over an `Aggregator`, to compute any statistics for each station.
+    /// It uses it to keep the sum of squares of each station for the variance and standard deviation.
+    vgeneric [squares],
+    /// Keeps the exact histogram of each station for the median and other quantiles, using `vgeneric`.
+    /// It is not the default version, since the histograms make it slower.
+    vhist [squares, histogram],
+    /// Accepts values with any number of decimals and keeps a `QuantileSketch` of each station for approximate quantiles.
+    /// The other statistics are computed from the values rounded to tenths.
+    vsketch [squares, sketch, any_decimals],
+}
@@ -1,7 +1,7 @@
 use brc::cli::Cli;
 use clap::Parser;
 
-pub fn main() {
+pub fn main() -> anyhow::Result<()> {
     let args = Cli::parse();
-    args.run();
+    args.run()
 }
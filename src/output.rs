@@ -0,0 +1,395 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{StationStats, Summary};
+
+/// The formats a `Summary` can be written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// The 1BRC format, `{name=min/mean/max, ...}`. Station names are written as they are.
+    #[default]
+    Text,
+    /// A JSON object with an object of statistics for each station.
+    Json,
+    /// CSV with the header `station,min,mean,max,count`.
+    Csv,
+    /// A JSON object per line for each station.
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// The columns written if no others are selected.
+    pub fn default_columns(self) -> &'static [Column] {
+        match self {
+            Self::Text => &[Column::Min, Column::Mean, Column::Max],
+            Self::Json | Self::Csv | Self::Ndjson => {
+                &[Column::Min, Column::Mean, Column::Max, Column::Count]
+            }
+        }
+    }
+}
+
+/// The statistics that can be written for each station.
+/// Parsed from and displayed as the name used in the CSV header and as a JSON key, e.g. `std_dev` or `p99.9`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Min,
+    Mean,
+    Max,
+    Count,
+    Sum,
+    /// The population variance. Requires the sum of squares of each station.
+    Variance,
+    /// The population standard deviation. Requires the sum of squares of each station.
+    StdDev,
+    /// The median. Requires the histogram or sketch of each station, and is only exact with the histogram.
+    Median,
+    /// The percentile given in tenths of a percent, so `p99.9` is 999.
+    /// Requires the histogram or sketch of each station, and is only exact with the histogram.
+    Percentile(u16),
+}
+
+impl Column {
+    /// The columns that can be parsed by name, apart from the percentiles.
+    const NAMED: [Self; 8] = [
+        Self::Min,
+        Self::Mean,
+        Self::Max,
+        Self::Count,
+        Self::Sum,
+        Self::Variance,
+        Self::StdDev,
+        Self::Median,
+    ];
+
+    /// Whether the column can only be computed from the histogram or sketch of each station.
+    pub fn is_quantile(self) -> bool {
+        matches!(self, Self::Median | Self::Percentile(_))
+    }
+
+    /// Whether the column can only be computed from the sum of squares of each station.
+    pub fn needs_squares(self) -> bool {
+        matches!(self, Self::Variance | Self::StdDev)
+    }
+
+    /// Writes the value for the station, or `missing` if the station does not have what the column needs:
+    /// the sum of squares for the variance and standard deviation, and a histogram or sketch for the quantiles.
+    /// Exact quantiles are written with a single decimal like the other temperatures, and approximate ones as they are.
+    fn write_value(
+        self,
+        f: &mut Formatter<'_>,
+        station: &StationStats,
+        missing: &str,
+    ) -> fmt::Result {
+        let quantile = match self {
+            Self::Min => return write!(f, "{}", station.display_min()),
+            Self::Mean => return write!(f, "{}", station.display_mean()),
+            Self::Max => return write!(f, "{}", station.display_max()),
+            Self::Count => return write!(f, "{}", station.count()),
+            Self::Sum => return write!(f, "{}", station.display_sum()),
+            Self::Variance => return write_two_decimals(f, station.variance(), missing),
+            Self::StdDev => return write_two_decimals(f, station.std_dev(), missing),
+            Self::Median => 0.5,
+            Self::Percentile(per_mille) => per_mille as f64 / 1000.,
+        };
+        if let Some(value) = station.display_quantile(quantile) {
+            write!(f, "{value}")
+        } else if let Some(sketch) = station.sketch() {
+            write!(f, "{}", sketch.quantile(quantile))
+        } else {
+            f.write_str(missing)
+        }
+    }
+}
+
+impl Display for Column {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Min => f.write_str("min"),
+            Self::Mean => f.write_str("mean"),
+            Self::Max => f.write_str("max"),
+            Self::Count => f.write_str("count"),
+            Self::Sum => f.write_str("sum"),
+            Self::Variance => f.write_str("variance"),
+            Self::StdDev => f.write_str("std_dev"),
+            Self::Median => f.write_str("median"),
+            Self::Percentile(per_mille) if per_mille % 10 == 0 => write!(f, "p{}", per_mille / 10),
+            Self::Percentile(per_mille) => write!(f, "p{}.{}", per_mille / 10, per_mille % 10),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(column) = Self::NAMED
+            .into_iter()
+            .find(|column| column.to_string() == s)
+        {
+            return Ok(column);
+        }
+        let invalid = || {
+            let names = Self::NAMED.map(|column| column.to_string()).join(", ");
+            format!(
+                "Invalid column {s:?}. Expected one of {names} or a percentile like p95 or p99.9."
+            )
+        };
+        let percentile = s.strip_prefix('p').ok_or_else(invalid)?;
+        let (whole, tenth) = percentile.split_once('.').unwrap_or((percentile, "0"));
+        let is_number = |digits: &str, max_len| {
+            (1..=max_len).contains(&digits.len()) && digits.bytes().all(|b| b.is_ascii_digit())
+        };
+        if !is_number(whole, 3) || !is_number(tenth, 1) {
+            return Err(invalid());
+        }
+        let per_mille = whole.parse::<u16>().unwrap() * 10 + tenth.parse::<u16>().unwrap();
+        if per_mille > 1000 {
+            return Err(invalid());
+        }
+        Ok(Self::Percentile(per_mille))
+    }
+}
+
+/// Displays a summary in the given format. Created by `Summary::display_as`.
+pub struct DisplayAs<'a> {
+    summary: &'a Summary,
+    format: OutputFormat,
+    columns: &'a [Column],
+}
+
+impl Summary {
+    /// Display the summary in the given format, e.g. with `to_string` or `print!`.
+    pub fn display_as(&self, format: OutputFormat) -> DisplayAs<'_> {
+        self.display_columns(format, format.default_columns())
+    }
+
+    /// Display the given columns of the summary in the given format.
+    /// In the text format the values are separated by slashes like the usual `min/mean/max`.
+    pub fn display_columns<'a>(
+        &'a self,
+        format: OutputFormat,
+        columns: &'a [Column],
+    ) -> DisplayAs<'a> {
+        DisplayAs {
+            summary: self,
+            format,
+            columns,
+        }
+    }
+}
+
+/// Writes the value with two decimals, or `missing` if there is none.
+fn write_two_decimals(f: &mut Formatter<'_>, value: Option<f64>, missing: &str) -> fmt::Result {
+    match value {
+        Some(value) => write!(f, "{value:.2}"),
+        None => f.write_str(missing),
+    }
+}
+
+/// Writes a string as a JSON string, with quotes and escapes.
+fn write_json_string(f: &mut Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str(&serde_json::to_string(s).expect("A string should always be serializable."))
+}
+
+/// Writes a CSV field, quoting it if it contains a delimiter, quote or line break.
+fn write_csv_field(f: &mut Formatter<'_>, s: &str) -> fmt::Result {
+    if s.contains([',', '"', '\n', '\r']) {
+        write!(f, "\"{}\"", s.replace('"', "\"\""))
+    } else {
+        f.write_str(s)
+    }
+}
+
+impl DisplayAs<'_> {
+    /// Writes the values of the columns for a station separated by the given separator.
+    /// Values the station does not have the statistics for, like quantiles without a histogram, are written as `missing`.
+    fn write_values(
+        &self,
+        f: &mut Formatter<'_>,
+        station: &StationStats,
+        separator: &str,
+        missing: &str,
+    ) -> fmt::Result {
+        for (index, column) in self.columns.iter().enumerate() {
+            if index > 0 {
+                f.write_str(separator)?;
+            }
+            column.write_value(f, station, missing)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the columns for a station as the fields of a JSON object, without the braces.
+    fn write_json_fields(&self, f: &mut Formatter<'_>, station: &StationStats) -> fmt::Result {
+        for (index, column) in self.columns.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "\"{column}\":")?;
+            column.write_value(f, station, "null")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for DisplayAs<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.format {
+            OutputFormat::Text => {
+                f.write_str("{")?;
+                for (index, station) in self.summary.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}=", station.name())?;
+                    self.write_values(f, station, "/", "-")?;
+                }
+                f.write_str("}\n")
+            }
+            OutputFormat::Json => {
+                f.write_str("{")?;
+                for (index, station) in self.summary.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_json_string(f, station.name())?;
+                    f.write_str(":{")?;
+                    self.write_json_fields(f, station)?;
+                    f.write_str("}")?;
+                }
+                f.write_str("}\n")
+            }
+            OutputFormat::Csv => {
+                f.write_str("station")?;
+                for column in self.columns {
+                    write!(f, ",{column}")?;
+                }
+                f.write_str("\n")?;
+                for station in self.summary {
+                    write_csv_field(f, station.name())?;
+                    f.write_str(",")?;
+                    self.write_values(f, station, ",", "")?;
+                    f.write_str("\n")?;
+                }
+                Ok(())
+            }
+            OutputFormat::Ndjson => {
+                for station in self.summary {
+                    f.write_str("{\"station\":")?;
+                    write_json_string(f, station.name())?;
+                    f.write_str(",")?;
+                    self.write_json_fields(f, station)?;
+                    f.write_str("}\n")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{histogram::Histogram, sketch::QuantileSketch};
+
+    #[test]
+    fn escaping() {
+        let summary = Summary::from_stations([
+            StationStats::new("Hamburg", 120, 120, 120, 1),
+            StationStats::new("a,\"b\"=c}", -15, 5, -1, 2),
+        ]);
+        assert_eq!(
+            summary.display_as(OutputFormat::Json).to_string(),
+            "{\"Hamburg\":{\"min\":12.0,\"mean\":12.0,\"max\":12.0,\"count\":1},\
+             \"a,\\\"b\\\"=c}\":{\"min\":-1.5,\"mean\":-0.1,\"max\":0.5,\"count\":2}}\n"
+        );
+        assert_eq!(
+            summary.display_as(OutputFormat::Csv).to_string(),
+            "station,min,mean,max,count\nHamburg,12.0,12.0,12.0,1\n\"a,\"\"b\"\"=c}\",-1.5,-0.1,0.5,2\n"
+        );
+        assert_eq!(
+            summary.display_as(OutputFormat::Ndjson).to_string(),
+            "{\"station\":\"Hamburg\",\"min\":12.0,\"mean\":12.0,\"max\":12.0,\"count\":1}\n\
+             {\"station\":\"a,\\\"b\\\"=c}\",\"min\":-1.5,\"mean\":-0.1,\"max\":0.5,\"count\":2}\n"
+        );
+        assert_eq!(
+            summary.display_as(OutputFormat::Text).to_string(),
+            summary.to_string()
+        );
+    }
+
+    #[test]
+    fn columns() {
+        // The values are -1.5 and 0.5, and b has no sum of squares.
+        let summary = Summary::from_stations([
+            StationStats::new("a", -15, 5, -10, 2).with_sum_squares(250),
+            StationStats::new("b", 0, 0, 0, 1),
+        ]);
+        let columns = [Column::Sum, Column::Count, Column::Variance, Column::StdDev];
+        assert_eq!(
+            summary
+                .display_columns(OutputFormat::Text, &columns)
+                .to_string(),
+            "{a=-1.0/2/1.00/1.00, b=0.0/1/-/-}\n"
+        );
+        assert_eq!(
+            summary
+                .display_columns(OutputFormat::Csv, &columns)
+                .to_string(),
+            "station,sum,count,variance,std_dev\na,-1.0,2,1.00,1.00\nb,0.0,1,,\n"
+        );
+        assert_eq!(
+            summary
+                .display_columns(OutputFormat::Ndjson, &columns)
+                .to_string(),
+            "{\"station\":\"a\",\"sum\":-1.0,\"count\":2,\"variance\":1.00,\"std_dev\":1.00}\n\
+             {\"station\":\"b\",\"sum\":0.0,\"count\":1,\"variance\":null,\"std_dev\":null}\n"
+        );
+    }
+
+    #[test]
+    fn quantiles() {
+        let histogram = Histogram::from_bins(vec![(-15, 1), (5, 2), (120, 1)]).unwrap();
+        let summary = Summary::from_stations([
+            StationStats::new("a", -15, 120, 115, 4).with_histogram(histogram),
+            StationStats::new("b", 0, 0, 0, 1),
+        ]);
+        let columns = ["median", "p5", "p99.9", "p100"].map(|name| name.parse().unwrap());
+        assert_eq!(
+            columns.map(|column: Column| column.to_string()),
+            ["median", "p5", "p99.9", "p100"]
+        );
+        assert_eq!(
+            summary
+                .display_columns(OutputFormat::Text, &columns)
+                .to_string(),
+            "{a=0.5/-1.5/12.0/12.0, b=-/-/-/-}\n"
+        );
+        assert_eq!(
+            summary
+                .display_columns(OutputFormat::Ndjson, &columns[..2])
+                .to_string(),
+            "{\"station\":\"a\",\"median\":0.5,\"p5\":-1.5}\n\
+             {\"station\":\"b\",\"median\":null,\"p5\":null}\n"
+        );
+        let mut sketch = QuantileSketch::default();
+        for value in [-1.25, 0.5, 0.5, 12.0] {
+            sketch.update(value);
+        }
+        let summary =
+            Summary::from_stations([StationStats::new("a", -13, 120, 117, 4).with_sketch(sketch)]);
+        assert_eq!(
+            summary
+                .display_columns(OutputFormat::Text, &columns)
+                .to_string(),
+            "{a=0.5/-1.25/12/12}\n"
+        );
+
+        for invalid in ["p", "p100.1", "p1.25", "p-1", "P5", "median "] {
+            assert!(invalid.parse::<Column>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,321 @@
+/// The default accuracy parameter of `QuantileSketch`.
+pub const DEFAULT_K: u16 = 200;
+/// No level of a sketch is compacted while it holds fewer items than this.
+const MIN_LEVEL_CAPACITY: usize = 8;
+
+/// An approximation of the distribution of arbitrary values using a KLL sketch
+/// (Karnin, Lang and Liberty, "Optimal Quantile Approximation in Streams", 2016).
+///
+/// Unlike `Histogram` this does not depend on values being in tenths of a degree, and it uses `O(k)` memory
+/// no matter how many values it summarizes. Sketches of disjoint sets of values can be merged at any time.
+///
+/// The error is in the rank of the returned value: for `q`-quantiles of `n` values, the returned value has a rank
+/// between `(q - ε) * n` and `(q + ε) * n` among the values, where `ε` shrinks proportionally to `1 / k`.
+/// With the default `k` of 200, `ε` is typically around 0.5% and below 1.5% with high probability,
+/// also after any number of merges.
+/// The minimum and maximum are always exact.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantileSketch {
+    k: u16,
+    count: u64,
+    min: f64,
+    max: f64,
+    /// The items of each level, where an item at level `h` stands for `2^h` values.
+    levels: Vec<Vec<f64>>,
+    /// The capacity of each level, which only changes with `k` and the number of levels.
+    capacities: Vec<usize>,
+}
+
+// Values are always finite, so equality is reflexive.
+impl Eq for QuantileSketch {}
+
+impl Default for QuantileSketch {
+    fn default() -> Self {
+        Self::new(DEFAULT_K)
+    }
+}
+
+impl QuantileSketch {
+    /// Creates an empty sketch with the given accuracy parameter. Larger values of `k` give smaller errors.
+    ///
+    /// Panics if `k` is less than 8.
+    pub fn new(k: u16) -> Self {
+        assert!(
+            k as usize >= MIN_LEVEL_CAPACITY,
+            "The accuracy parameter should be at least {MIN_LEVEL_CAPACITY}."
+        );
+        let mut sketch = Self {
+            k,
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            levels: vec![vec![]],
+            capacities: vec![],
+        };
+        sketch.update_capacities();
+        sketch
+    }
+
+    /// Recreates a sketch from the parts returned by `k`, `min`, `max` and `levels`.
+    /// Returns `None` if the parts could not have come from a sketch.
+    pub fn from_parts(k: u16, min: f64, max: f64, levels: Vec<Vec<f64>>) -> Option<Self> {
+        let count = levels
+            .iter()
+            .enumerate()
+            .try_fold(0u64, |count, (level, items)| {
+                let weight = 1u64.checked_shl(level as u32)?;
+                count.checked_add(weight.checked_mul(items.len() as u64)?)
+            })?;
+        let valid = k as usize >= MIN_LEVEL_CAPACITY
+            && !levels.is_empty()
+            && count > 0
+            && min.is_finite()
+            && max.is_finite()
+            && levels
+                .iter()
+                .flatten()
+                .all(|&item| (min..=max).contains(&item));
+        if !valid {
+            return None;
+        }
+        let mut sketch = Self {
+            k,
+            count,
+            min,
+            max,
+            levels,
+            capacities: vec![],
+        };
+        sketch.update_capacities();
+        Some(sketch)
+    }
+
+    /// The accuracy parameter of the sketch.
+    pub fn k(&self) -> u16 {
+        self.k
+    }
+
+    /// The number of values summarized by the sketch.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The smallest value, or infinity if the sketch is empty.
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    /// The largest value, or negative infinity if the sketch is empty.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// The items kept at each level, where an item at level `h` stands for `2^h` values.
+    pub fn levels(&self) -> &[Vec<f64>] {
+        &self.levels
+    }
+
+    /// Adds a value to the sketch.
+    ///
+    /// Panics if the value is not finite.
+    pub fn update(&mut self, value: f64) {
+        assert!(value.is_finite(), "Only finite values can be sketched.");
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.count += 1;
+        self.levels[0].push(value);
+        // Only level 0 grows here, so the levels are only compacted once it is full.
+        if self.levels[0].len() >= self.capacities[0] {
+            self.compress();
+        }
+    }
+
+    /// Combines the sketches of two disjoint sets of values.
+    /// The result has the accuracy of the less accurate of the two.
+    pub fn merge(&mut self, other: &Self) {
+        self.k = self.k.min(other.k);
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        if self.levels.len() < other.levels.len() {
+            self.levels.resize(other.levels.len(), vec![]);
+        }
+        self.update_capacities();
+        for (items, other_items) in self.levels.iter_mut().zip(&other.levels) {
+            items.extend(other_items);
+        }
+        self.compress();
+    }
+
+    /// The approximate `q`-quantile, i.e. the smallest value such that about a fraction `q` of the values
+    /// are at most that value, with the rank error described for `QuantileSketch`.
+    ///
+    /// Panics if `q` is not between 0 and 1 or the sketch is empty.
+    pub fn quantile(&self, q: f64) -> f64 {
+        assert!(
+            (0. ..=1.).contains(&q),
+            "The quantile should be between 0 and 1."
+        );
+        assert!(self.count > 0, "An empty sketch has no quantiles.");
+        let rank = ((q * self.count as f64).ceil() as u64).clamp(1, self.count);
+        if rank == 1 {
+            return self.min;
+        }
+        if rank == self.count {
+            return self.max;
+        }
+        let mut items: Vec<(f64, u64)> = self
+            .levels
+            .iter()
+            .enumerate()
+            .flat_map(|(level, items)| items.iter().map(move |&item| (item, 1 << level)))
+            .collect();
+        items.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut seen = 0;
+        for (item, weight) in items {
+            seen += weight;
+            if seen >= rank {
+                return item;
+            }
+        }
+        self.max
+    }
+
+    /// Computes the number of items each level can hold before it is compacted.
+    /// The top level holds `k` items and each level below it two thirds of the one above.
+    fn update_capacities(&mut self) {
+        let num_levels = self.levels.len();
+        self.capacities = (0..num_levels)
+            .map(|level| {
+                let depth = (num_levels - 1 - level) as i32;
+                let capacity = (self.k as f64 * (2. / 3_f64).powi(depth)).ceil() as usize;
+                capacity.max(MIN_LEVEL_CAPACITY)
+            })
+            .collect();
+    }
+
+    /// Compacts levels until the number of items fits in the total capacity of the levels.
+    /// Compacting a level sorts it and moves every other item to the level above, doubling their weight.
+    fn compress(&mut self) {
+        loop {
+            let num_items: usize = self.levels.iter().map(Vec::len).sum();
+            let total_capacity: usize = self.capacities.iter().sum();
+            if num_items <= total_capacity {
+                return;
+            }
+            let level = (0..self.levels.len())
+                .find(|&h| self.levels[h].len() >= self.capacities[h])
+                .expect("Some level is over capacity when the sketch is.");
+            if level + 1 == self.levels.len() {
+                self.levels.push(vec![]);
+                self.update_capacities();
+            }
+            let mut items = std::mem::take(&mut self.levels[level]);
+            items.sort_by(f64::total_cmp);
+            // An odd item out stays behind, so only pairs are compacted.
+            if items.len() % 2 == 1 {
+                self.levels[level].push(items.pop().unwrap());
+            }
+            let offset = self.coin(level);
+            let promoted = items.into_iter().skip(offset).step_by(2);
+            self.levels[level + 1].extend(promoted);
+        }
+    }
+
+    /// Chooses whether a compaction keeps the even or the odd items.
+    /// This is pseudo-random but deterministic, so the same input always gives the same sketch.
+    fn coin(&self, level: usize) -> usize {
+        // The finalizer of SplitMix64.
+        let mut z = self.count ^ ((level as u64) << 58);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z & 1) as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The largest difference between the requested and the actual rank of the quantiles of `0..n`, relative to `n`.
+    fn max_rank_error(sketch: &QuantileSketch, n: u64) -> f64 {
+        (0..=100)
+            .map(|percent| {
+                let q = percent as f64 / 100.;
+                // The value i has rank i + 1 among 0..n.
+                let rank = sketch.quantile(q) + 1.;
+                (rank - (q * n as f64).ceil().max(1.)).abs() / n as f64
+            })
+            .fold(0., f64::max)
+    }
+
+    /// The values 0..n in a scrambled order.
+    fn scrambled(n: u64) -> impl Iterator<Item = f64> {
+        // The stride is coprime to the n used in the tests, so this visits every value below n exactly once.
+        const STRIDE: u64 = 618_033;
+        (0..n).map(move |i| (i * STRIDE % n) as f64)
+    }
+
+    #[test]
+    fn small() {
+        let mut sketch = QuantileSketch::default();
+        for value in [3.25, -1.5, 7.125, 0.] {
+            sketch.update(value);
+        }
+        // Nothing is compacted yet, so the quantiles are exact.
+        assert_eq!(sketch.count(), 4);
+        assert_eq!(sketch.quantile(0.), -1.5);
+        assert_eq!(sketch.quantile(0.5), 0.);
+        assert_eq!(sketch.quantile(0.75), 3.25);
+        assert_eq!(sketch.quantile(1.), 7.125);
+    }
+
+    #[test]
+    fn error_bound() {
+        let n = 1_000_000;
+        let mut sketch = QuantileSketch::default();
+        for value in scrambled(n) {
+            sketch.update(value);
+        }
+        assert_eq!(sketch.count(), n);
+        assert!(sketch.levels().iter().map(Vec::len).sum::<usize>() < 1000);
+        assert!(max_rank_error(&sketch, n) < 0.015);
+
+        // Sorted input is the classic worst case for compaction.
+        let mut sketch = QuantileSketch::default();
+        for value in 0..n {
+            sketch.update(value as f64);
+        }
+        assert!(max_rank_error(&sketch, n) < 0.015);
+    }
+
+    #[test]
+    fn merge() {
+        let n = 1_000_000;
+        let mut sketches = vec![QuantileSketch::default(); 13];
+        for (index, value) in scrambled(n).enumerate() {
+            sketches[index % 13].update(value);
+        }
+        let mut merged = QuantileSketch::default();
+        for sketch in &sketches {
+            merged.merge(sketch);
+        }
+        assert_eq!(merged.count(), n);
+        assert_eq!(merged.min(), 0.);
+        assert_eq!(merged.max(), (n - 1) as f64);
+        assert!(max_rank_error(&merged, n) < 0.015);
+
+        let parts = QuantileSketch::from_parts(
+            merged.k(),
+            merged.min(),
+            merged.max(),
+            merged.levels().to_vec(),
+        );
+        assert_eq!(parts, Some(merged));
+        assert_eq!(
+            QuantileSketch::from_parts(200, 0., 1., vec![vec![2.]]),
+            None
+        );
+    }
+}
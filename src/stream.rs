@@ -0,0 +1,97 @@
+use std::{
+    io::{self, Read},
+    sync::mpsc::{self, SyncSender},
+    thread,
+};
+
+use anyhow::{ensure, Result};
+
+use crate::{ParseError, ParseMode, SummarizeBytesFn, Summary};
+
+/// The default number of bytes read from the source at a time.
+pub const DEFAULT_BUFFER_SIZE: usize = 1 << 26;
+
+/// A part of the input ending at a line boundary, along with where it starts in the full input.
+struct Chunk {
+    data: Vec<u8>,
+    offset: usize,
+    lines: usize,
+}
+
+/// Summarize everything read from `reader` without needing the whole input in memory or in a file.
+/// Buffers of `buffer_size` bytes are read on a separate thread while the previous one is summarized.
+/// Only complete lines are handed to `summarize_bytes`, so a line cut off at the end of a buffer
+/// is carried over to the next one.
+pub fn summarize_reader(
+    reader: impl Read + Send,
+    summarize_bytes: SummarizeBytesFn,
+    num_slices: usize,
+    mode: ParseMode,
+    buffer_size: usize,
+) -> Result<Summary> {
+    ensure!(buffer_size > 0, "The buffer size should be positive.");
+    thread::scope(|scope| {
+        // Allow the next chunk to be read while the current one is summarized.
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let reader = scope.spawn(move || read_chunks(reader, buffer_size, sender));
+
+        let mut summary = Summary::new();
+        for chunk in receiver {
+            let chunk_summary =
+                summarize_bytes(&chunk.data, None, num_slices, mode).map_err(|error| {
+                    match error.downcast::<ParseError>() {
+                        Ok(error) => error.offset_by_position(chunk.offset, chunk.lines).into(),
+                        Err(error) => error,
+                    }
+                })?;
+            summary = summary.merge(chunk_summary);
+        }
+        reader
+            .join()
+            .expect("The reader thread should not panic.")?;
+        Ok(summary)
+    })
+}
+
+/// Read chunks of whole lines from the reader and send them until the reader is exhausted
+/// or the receiving end hangs up.
+fn read_chunks(
+    mut reader: impl Read,
+    buffer_size: usize,
+    sender: SyncSender<Chunk>,
+) -> io::Result<()> {
+    let mut offset = 0;
+    let mut lines = 0;
+    let mut buffer = Vec::with_capacity(buffer_size);
+    loop {
+        let read = (&mut reader)
+            .take(buffer_size as u64)
+            .read_to_end(&mut buffer)?;
+        let at_end = read < buffer_size;
+        let chunk_len = if at_end {
+            buffer.len()
+        } else if let Some(last_newline) = buffer.iter().rposition(|&c| c == b'\n') {
+            last_newline + 1
+        } else {
+            // The line does not fit in the buffer, so keep reading until it ends.
+            continue;
+        };
+        if chunk_len == 0 {
+            return Ok(());
+        }
+
+        let mut rest = Vec::with_capacity(buffer.len() - chunk_len + buffer_size);
+        rest.extend_from_slice(&buffer[chunk_len..]);
+        buffer.truncate(chunk_len);
+        let chunk = Chunk {
+            data: std::mem::replace(&mut buffer, rest),
+            offset,
+            lines,
+        };
+        offset += chunk.data.len();
+        lines += chunk.data.iter().filter(|&&c| c == b'\n').count();
+        if sender.send(chunk).is_err() || at_end {
+            return Ok(());
+        }
+    }
+}
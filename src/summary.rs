@@ -0,0 +1,547 @@
+use std::{cmp::Ordering, fmt};
+
+use crate::{histogram::Histogram, sketch::QuantileSketch, SkippedLines};
+
+/// Statistics for a single station.
+///
+/// All temperatures are kept as integers in tenths of a degree, so the statistics are exact.
+/// E.g. a `min_tenths` of `-153` corresponds to a minimum temperature of -15.3.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StationStats {
+    name: String,
+    min: i32,
+    max: i32,
+    sum: i64,
+    sum_squares: Option<u64>,
+    count: u64,
+    histogram: Option<Histogram>,
+    sketch: Option<QuantileSketch>,
+}
+
+impl StationStats {
+    /// Creates the statistics for a station from values given in tenths of a degree.
+    ///
+    /// Panics if `count` is zero, since a station without measurements has no statistics.
+    pub fn new(name: impl Into<String>, min: i32, max: i32, sum: i64, count: u64) -> Self {
+        assert!(count > 0, "A station should have at least one measurement.");
+        Self {
+            name: name.into(),
+            min,
+            max,
+            sum,
+            sum_squares: None,
+            count,
+            histogram: None,
+            sketch: None,
+        }
+    }
+
+    /// Adds the sum of the squares of the values in tenths, which is needed for the variance and standard deviation.
+    pub fn with_sum_squares(self, sum_squares: u64) -> Self {
+        Self {
+            sum_squares: Some(sum_squares),
+            ..self
+        }
+    }
+
+    /// Adds the exact distribution of the measurements, which is needed for the median and other quantiles.
+    ///
+    /// Panics if the histogram does not have the same number of measurements as the station.
+    pub fn with_histogram(self, histogram: Histogram) -> Self {
+        assert_eq!(
+            histogram.count(),
+            self.count,
+            "The histogram should contain every measurement of the station."
+        );
+        Self {
+            histogram: Some(histogram),
+            ..self
+        }
+    }
+
+    /// Adds an approximation of the distribution of the measurements,
+    /// which gives the median and other quantiles if there is no histogram.
+    ///
+    /// Panics if the sketch does not have the same number of measurements as the station.
+    pub fn with_sketch(self, sketch: QuantileSketch) -> Self {
+        assert_eq!(
+            sketch.count(),
+            self.count,
+            "The sketch should contain every measurement of the station."
+        );
+        Self {
+            sketch: Some(sketch),
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The minimum temperature in tenths of a degree.
+    pub fn min_tenths(&self) -> i32 {
+        self.min
+    }
+
+    /// The maximum temperature in tenths of a degree.
+    pub fn max_tenths(&self) -> i32 {
+        self.max
+    }
+
+    /// The sum of all temperatures in tenths of a degree.
+    pub fn sum_tenths(&self) -> i64 {
+        self.sum
+    }
+
+    /// The sum of the squares of all temperatures in tenths of a degree, if it was kept.
+    pub fn sum_squares_tenths(&self) -> Option<u64> {
+        self.sum_squares
+    }
+
+    /// The number of measurements for the station.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The exact distribution of the measurements, if it was kept.
+    pub fn histogram(&self) -> Option<&Histogram> {
+        self.histogram.as_ref()
+    }
+
+    /// The approximate distribution of the measurements, if it was kept.
+    pub fn sketch(&self) -> Option<&QuantileSketch> {
+        self.sketch.as_ref()
+    }
+
+    /// The mean temperature in tenths of a degree, rounded half away from zero.
+    /// This is the value used in the output.
+    pub fn mean_tenths(&self) -> i64 {
+        let count = self.count as i64;
+        let total = self.sum.abs();
+        let mean = total / count + if (total % count) * 2 >= count { 1 } else { 0 };
+        if self.sum < 0 {
+            -mean
+        } else {
+            mean
+        }
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min as f64 / 10.
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max as f64 / 10.
+    }
+
+    /// The exact mean temperature, without any rounding to tenths.
+    pub fn mean(&self) -> f64 {
+        self.sum as f64 / (self.count as f64 * 10.)
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum as f64 / 10.
+    }
+
+    /// The `q`-quantile of the temperatures, if the histogram or sketch was kept.
+    /// It is exact if there is a histogram, see `Histogram::quantile_tenths`,
+    /// and approximate otherwise, see `QuantileSketch::quantile`.
+    ///
+    /// Panics if `q` is not between 0 and 1.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        match (&self.histogram, &self.sketch) {
+            (Some(histogram), _) => Some(histogram.quantile_tenths(q) as f64 / 10.),
+            (None, Some(sketch)) => Some(sketch.quantile(q)),
+            (None, None) => None,
+        }
+    }
+
+    /// The median temperature, if the histogram or sketch was kept.
+    pub fn median(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+
+    /// The population variance of the temperatures, if the sum of squares was kept.
+    /// It is computed from the exact integer sums, so the only rounding is in the final division.
+    pub fn variance(&self) -> Option<f64> {
+        let count = self.count as i128;
+        let sum = self.sum as i128;
+        let numerator = count * self.sum_squares? as i128 - sum * sum;
+        Some(numerator as f64 / (count * count * 100) as f64)
+    }
+
+    /// The population standard deviation of the temperatures, if the sum of squares was kept.
+    pub fn std_dev(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+
+    /// The minimum temperature formatted with a single decimal.
+    pub(crate) fn display_min(&self) -> Tenths {
+        Tenths::new(self.min < 0, self.min as i64)
+    }
+
+    /// The mean temperature formatted with a single decimal.
+    /// The sign follows the sum, so a slightly negative mean is written as -0.0.
+    pub(crate) fn display_mean(&self) -> Tenths {
+        Tenths::new(self.sum < 0, self.mean_tenths())
+    }
+
+    /// The maximum temperature formatted with a single decimal.
+    pub(crate) fn display_max(&self) -> Tenths {
+        Tenths::new(self.max < 0, self.max as i64)
+    }
+
+    /// The sum of the temperatures formatted with a single decimal.
+    pub(crate) fn display_sum(&self) -> Tenths {
+        Tenths::new(self.sum < 0, self.sum)
+    }
+
+    /// The `q`-quantile of the temperatures formatted with a single decimal, if the histogram was kept.
+    pub(crate) fn display_quantile(&self, q: f64) -> Option<Tenths> {
+        self.histogram.as_ref().map(|histogram| {
+            let quantile = histogram.quantile_tenths(q);
+            Tenths::new(quantile < 0, quantile as i64)
+        })
+    }
+
+    /// Combines the statistics of two disjoint sets of measurements for the same station.
+    pub fn merge(&mut self, other: &Self) {
+        debug_assert_eq!(self.name, other.name);
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+        // The sum of squares and histogram are only kept if both sides have them, since otherwise they would be incomplete.
+        self.sum_squares = self
+            .sum_squares
+            .zip(other.sum_squares)
+            .map(|(sum_squares, other)| sum_squares + other);
+        self.histogram = match (self.histogram.take(), &other.histogram) {
+            (Some(mut histogram), Some(other_histogram)) => {
+                histogram.merge(other_histogram);
+                Some(histogram)
+            }
+            _ => None,
+        };
+        self.sketch = match (self.sketch.take(), &other.sketch) {
+            (Some(mut sketch), Some(other_sketch)) => {
+                sketch.merge(other_sketch);
+                Some(sketch)
+            }
+            _ => None,
+        };
+    }
+}
+
+/// A value given in tenths of a degree, formatted with a single decimal.
+pub(crate) struct Tenths {
+    negative: bool,
+    tenths: i64,
+}
+
+impl Tenths {
+    fn new(negative: bool, tenths: i64) -> Self {
+        Self {
+            negative,
+            tenths: tenths.abs(),
+        }
+    }
+}
+
+impl fmt::Display for Tenths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.negative { "-" } else { "" };
+        write!(f, "{sign}{}.{}", self.tenths / 10, self.tenths % 10)
+    }
+}
+
+impl fmt::Display for StationStats {
+    /// Formats the station as `name=min/mean/max`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}={}/{}/{}",
+            self.name,
+            self.display_min(),
+            self.display_mean(),
+            self.display_max()
+        )
+    }
+}
+
+/// The statistics of all stations in a dataset, sorted by station name,
+/// along with the number of invalid lines skipped if the input was parsed in lenient mode.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    stations: Vec<StationStats>,
+    skipped_lines: SkippedLines,
+}
+
+impl Summary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a summary from the statistics of a number of stations in any order.
+    /// Statistics for the same station are merged.
+    pub fn from_stations(stations: impl IntoIterator<Item = StationStats>) -> Self {
+        let mut stations: Vec<_> = stations.into_iter().collect();
+        stations.sort_by(|a, b| a.name.cmp(&b.name));
+        stations.dedup_by(|b, a| {
+            if a.name == b.name {
+                a.merge(b);
+                true
+            } else {
+                false
+            }
+        });
+        Self {
+            stations,
+            skipped_lines: SkippedLines::new(),
+        }
+    }
+
+    pub fn with_skipped_lines(self, skipped_lines: SkippedLines) -> Self {
+        Self {
+            skipped_lines,
+            ..self
+        }
+    }
+
+    /// The statistics of all stations sorted by station name.
+    pub fn stations(&self) -> &[StationStats] {
+        &self.stations
+    }
+
+    /// The number of invalid lines skipped for each reason.
+    pub fn skipped_lines(&self) -> &SkippedLines {
+        &self.skipped_lines
+    }
+
+    /// Find the statistics of the station with the given name.
+    pub fn get(&self, name: &str) -> Option<&StationStats> {
+        self.stations
+            .binary_search_by(|station| station.name.as_str().cmp(name))
+            .ok()
+            .map(|index| &self.stations[index])
+    }
+
+    pub fn len(&self) -> usize {
+        self.stations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stations.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, StationStats> {
+        self.stations.iter()
+    }
+
+    /// Combines two summaries of disjoint sets of measurements.
+    pub fn merge(self, other: Self) -> Self {
+        let skipped_lines = self.skipped_lines.merge(other.skipped_lines);
+        let mut result = Vec::with_capacity(self.len().max(other.len()));
+        let mut a_iter = self.into_iter();
+        let mut b_iter = other.into_iter();
+
+        let mut cur_a = a_iter.next();
+        let mut cur_b = b_iter.next();
+        loop {
+            if let Some(mut a) = cur_a {
+                if let Some(b) = cur_b {
+                    match a.name.cmp(&b.name) {
+                        Ordering::Less => {
+                            result.push(a);
+                            cur_a = a_iter.next();
+                            cur_b = Some(b);
+                        }
+                        Ordering::Equal => {
+                            a.merge(&b);
+                            result.push(a);
+                            cur_a = a_iter.next();
+                            cur_b = b_iter.next();
+                        }
+                        Ordering::Greater => {
+                            result.push(b);
+                            cur_a = Some(a);
+                            cur_b = b_iter.next();
+                        }
+                    }
+                } else {
+                    result.push(a);
+                    result.extend(a_iter);
+                    break;
+                }
+            } else {
+                result.extend(cur_b.into_iter().chain(b_iter));
+                break;
+            }
+        }
+        Self {
+            stations: result,
+            skipped_lines,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    /// Formats the summary as the final output, `{name=min/mean/max, ...}` followed by a newline.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("{")?;
+        let mut stations = self.stations.iter();
+        if let Some(station) = stations.next() {
+            write!(f, "{station}")?;
+        }
+        for station in stations {
+            write!(f, ", {station}")?;
+        }
+        f.write_str("}\n")
+    }
+}
+
+impl IntoIterator for Summary {
+    type Item = StationStats;
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.stations.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Summary {
+    type Item = &'a StationStats;
+    type IntoIter = std::slice::Iter<'a, StationStats>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.stations.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{
+        aggregator::{Aggregator, Buckets, Stats},
+        histogram::{MAX_TENTHS, MIN_TENTHS},
+    };
+
+    /// Summarize the values of each station exactly, keeping a histogram for the stations with an even number.
+    fn summarize(values: &[(u8, i32)]) -> Summary {
+        let mut stations = std::collections::BTreeMap::<u8, (Stats, Buckets)>::new();
+        for &(station, value) in values {
+            stations.entry(station).or_default().update(value);
+        }
+        Summary::from_stations(stations.into_iter().map(|(station, aggregator)| {
+            let (stats, histogram) = aggregator.finish();
+            let stats = stats.into_station_stats(station.to_string());
+            if station % 2 == 0 {
+                stats.with_histogram(histogram)
+            } else {
+                stats
+            }
+        }))
+    }
+
+    fn values() -> impl Strategy<Value = Vec<(u8, i32)>> {
+        proptest::collection::vec((0..6u8, MIN_TENTHS..=MAX_TENTHS), 0..50)
+    }
+
+    proptest! {
+        #[test]
+        fn merge_properties(a in values(), b in values(), c in values()) {
+            let (a_summary, b_summary, c_summary) = (summarize(&a), summarize(&b), summarize(&c));
+            prop_assert_eq!(
+                a_summary.clone().merge(b_summary.clone()),
+                b_summary.clone().merge(a_summary.clone())
+            );
+            prop_assert_eq!(
+                a_summary.clone().merge(b_summary.clone()).merge(c_summary.clone()),
+                a_summary.clone().merge(b_summary.clone().merge(c_summary))
+            );
+            let all = [a, b].concat();
+            prop_assert_eq!(summarize(&all), a_summary.merge(b_summary));
+        }
+    }
+
+    #[test]
+    fn display() {
+        let summary = Summary::from_stations([
+            StationStats::new("Petropavlovsk-Kamchatsky", -95, 95, 0, 2),
+            StationStats::new("Bosaso", -150, 200, 50, 4),
+        ]);
+        assert_eq!(
+            summary.to_string(),
+            "{Bosaso=-15.0/1.3/20.0, Petropavlovsk-Kamchatsky=-9.5/0.0/9.5}\n"
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let a = Summary::from_stations([
+            StationStats::new("a", 10, 20, 30, 2).with_sum_squares(500),
+            StationStats::new("c", -5, -5, -5, 1).with_sum_squares(25),
+        ]);
+        let b = Summary::from_stations([
+            StationStats::new("b", 0, 0, 0, 1),
+            StationStats::new("c", -15, 5, -10, 2).with_sum_squares(250),
+        ]);
+        let merged = a.merge(b);
+        assert_eq!(merged.len(), 3);
+        let c = merged.get("c").unwrap();
+        assert_eq!(c.min_tenths(), -15);
+        assert_eq!(c.max_tenths(), 5);
+        assert_eq!(c.sum_tenths(), -15);
+        assert_eq!(c.sum_squares_tenths(), Some(275));
+        assert_eq!(c.count(), 3);
+        assert_eq!(c.mean_tenths(), -5);
+        // The values are -0.5, -1.5 and 0.5.
+        assert!((c.variance().unwrap() - 2. / 3.).abs() < 1e-12);
+        assert_eq!(c.median(), None);
+        let a = merged.get("a").unwrap();
+        assert_eq!(a.sum_squares_tenths(), Some(500));
+        assert_eq!(merged.get("b").unwrap().variance(), None);
+        let mut b = merged.get("b").unwrap().clone();
+        b.merge(&StationStats::new("b", 0, 0, 0, 1).with_sum_squares(0));
+        assert_eq!(b.std_dev(), None);
+    }
+
+    #[test]
+    fn merge_histograms() {
+        let histogram = |bins| Histogram::from_bins(bins).unwrap();
+        let a = Summary::from_stations([
+            StationStats::new("a", -5, 5, 0, 2).with_histogram(histogram(vec![(-5, 1), (5, 1)])),
+            StationStats::new("b", 0, 0, 0, 1).with_histogram(histogram(vec![(0, 1)])),
+        ]);
+        let b = Summary::from_stations([
+            StationStats::new("a", 7, 7, 14, 2).with_histogram(histogram(vec![(7, 2)])),
+            StationStats::new("b", 1, 1, 1, 1),
+        ]);
+        let merged = a.merge(b);
+        let a = merged.get("a").unwrap();
+        assert_eq!(a.histogram().unwrap().bins(), [(-5, 1), (5, 1), (7, 2)]);
+        assert_eq!(a.median(), Some(0.5));
+        assert_eq!(a.quantile(1.), Some(0.7));
+        assert_eq!(merged.get("b").unwrap().histogram(), None);
+    }
+
+    #[test]
+    fn merge_sketches() {
+        let sketch = |values: &[f64]| {
+            let mut sketch = QuantileSketch::default();
+            values.iter().for_each(|&value| sketch.update(value));
+            sketch
+        };
+        let a = StationStats::new("a", -5, 5, 0, 2).with_sketch(sketch(&[-0.52, 0.48]));
+        let mut b = StationStats::new("a", 7, 7, 14, 2).with_sketch(sketch(&[0.7, 0.71]));
+        b.merge(&a);
+        assert_eq!(b.sketch().unwrap().count(), 4);
+        assert_eq!(b.median(), Some(0.48));
+        assert_eq!(b.quantile(1.), Some(0.71));
+        b.merge(&StationStats::new("a", 0, 0, 0, 1));
+        assert_eq!(b.median(), None);
+    }
+}
@@ -0,0 +1,246 @@
+use std::time::Duration;
+#[cfg(feature = "timing")]
+use std::{sync::Mutex, time::Instant};
+
+/// A phase of the pipeline described in `common`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    /// Opening and memory mapping the file. The pages are only faulted in as the slices read them.
+    Map,
+    /// Splitting the data into slices at line boundaries with `find_split_index`.
+    Split,
+    /// Summarizing the slices in parallel.
+    Slices,
+    /// Merging the tables of the slices.
+    Merge,
+    /// Turning the merged table into a `Summary`.
+    IntoResult,
+}
+
+impl Phase {
+    /// Every phase in the order they run.
+    pub const ALL: [Self; 5] = [
+        Self::Map,
+        Self::Split,
+        Self::Slices,
+        Self::Merge,
+        Self::IntoResult,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Map => "map",
+            Self::Split => "split",
+            Self::Slices => "slices",
+            Self::Merge => "merge",
+            Self::IntoResult => "into_result",
+        }
+    }
+}
+
+/// How long a single slice took to summarize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SliceTiming {
+    /// The index of the thread in the rayon pool that summarized the slice.
+    pub thread: Option<usize>,
+    pub bytes: usize,
+    pub duration: Duration,
+}
+
+/// The time spent in each phase and on each slice since the timings were last taken.
+///
+/// Only recorded with the `timing` feature, without which the timers compile to nothing and the timings are always empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Timings {
+    phases: [Duration; Phase::ALL.len()],
+    slices: Vec<SliceTiming>,
+    /// The minor page faults while summarizing files, which are mostly the pages of the map being read.
+    page_faults: u64,
+}
+
+impl Timings {
+    #[cfg(feature = "timing")]
+    const fn new() -> Self {
+        Self {
+            phases: [Duration::ZERO; Phase::ALL.len()],
+            slices: Vec::new(),
+            page_faults: 0,
+        }
+    }
+
+    pub fn phase(&self, phase: Phase) -> Duration {
+        self.phases[phase as usize]
+    }
+
+    pub fn slices(&self) -> &[SliceTiming] {
+        &self.slices
+    }
+
+    pub fn page_faults(&self) -> u64 {
+        self.page_faults
+    }
+
+    /// The time each thread spent summarizing slices, by the index of the thread.
+    pub fn thread_durations(&self) -> Vec<Duration> {
+        let mut durations = vec![];
+        for slice in &self.slices {
+            let thread = slice.thread.unwrap_or(0);
+            if durations.len() <= thread {
+                durations.resize(thread + 1, Duration::ZERO);
+            }
+            durations[thread] += slice.duration;
+        }
+        durations
+    }
+
+    /// How much longer the slowest slice took than the mean slice, which is 1 if they are perfectly balanced.
+    /// `None` if there are no slices.
+    pub fn slice_imbalance(&self) -> Option<f64> {
+        imbalance(self.slices.iter().map(|slice| slice.duration))
+    }
+
+    /// How much longer the busiest thread worked than the mean thread, which is 1 if they are perfectly balanced.
+    /// `None` if no thread summarized a slice.
+    pub fn thread_imbalance(&self) -> Option<f64> {
+        imbalance(self.thread_durations().into_iter())
+    }
+
+    /// Add the other timings to these.
+    pub fn merge(mut self, other: Self) -> Self {
+        for (duration, other) in self.phases.iter_mut().zip(other.phases) {
+            *duration += other;
+        }
+        self.slices.extend(other.slices);
+        self.page_faults += other.page_faults;
+        self
+    }
+}
+
+/// The largest duration divided by the mean of the durations.
+fn imbalance(durations: impl Iterator<Item = Duration>) -> Option<f64> {
+    let seconds: Vec<f64> = durations.map(|duration| duration.as_secs_f64()).collect();
+    let max = seconds.iter().copied().reduce(f64::max)?;
+    let mean = seconds.iter().sum::<f64>() / seconds.len() as f64;
+    Some(if mean > 0. { max / mean } else { 1. })
+}
+
+/// The timings recorded since they were last taken.
+#[cfg(feature = "timing")]
+static TIMINGS: Mutex<Timings> = Mutex::new(Timings::new());
+
+/// Run the function, recording the time it takes as part of the phase.
+#[inline(always)]
+pub(crate) fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "timing")]
+    {
+        let start = Instant::now();
+        let result = f();
+        TIMINGS.lock().unwrap().phases[phase as usize] += start.elapsed();
+        result
+    }
+    #[cfg(not(feature = "timing"))]
+    {
+        let _ = phase;
+        f()
+    }
+}
+
+/// Run the function summarizing a slice of the given length, recording the time it takes on the current thread.
+#[inline(always)]
+pub(crate) fn time_slice<T>(bytes: usize, f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "timing")]
+    {
+        let start = Instant::now();
+        let result = f();
+        let timing = SliceTiming {
+            thread: rayon::current_thread_index(),
+            bytes,
+            duration: start.elapsed(),
+        };
+        TIMINGS.lock().unwrap().slices.push(timing);
+        result
+    }
+    #[cfg(not(feature = "timing"))]
+    {
+        let _ = bytes;
+        f()
+    }
+}
+
+/// Run the function, recording the minor page faults of the process while it runs.
+#[inline(always)]
+pub(crate) fn count_page_faults<T>(f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "timing")]
+    {
+        let before = minor_page_faults();
+        let result = f();
+        if let (Some(before), Some(after)) = (before, minor_page_faults()) {
+            TIMINGS.lock().unwrap().page_faults += after - before;
+        }
+        result
+    }
+    #[cfg(not(feature = "timing"))]
+    f()
+}
+
+/// The minor page faults of the process so far, from the tenth field of `/proc/self/stat`, so only on Linux.
+#[cfg(feature = "timing")]
+fn minor_page_faults() -> Option<u64> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // The name of the process in the second field is in parentheses and may contain spaces.
+    let (_, fields) = stat.rsplit_once(") ")?;
+    fields.split(' ').nth(7)?.parse().ok()
+}
+
+/// Take the timings recorded by every thread since they were last taken.
+/// Always empty without the `timing` feature.
+pub fn take() -> Timings {
+    #[cfg(feature = "timing")]
+    return std::mem::take(&mut *TIMINGS.lock().unwrap());
+    #[cfg(not(feature = "timing"))]
+    Timings::default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn imbalance() {
+        let slice = |thread, millis| SliceTiming {
+            thread: Some(thread),
+            bytes: 100,
+            duration: Duration::from_millis(millis),
+        };
+        let timings = Timings {
+            slices: vec![slice(0, 10), slice(1, 30), slice(0, 20)],
+            ..Timings::default()
+        };
+        assert_eq!(
+            timings.thread_durations(),
+            [Duration::from_millis(30), Duration::from_millis(30)]
+        );
+        assert_eq!(timings.thread_imbalance(), Some(1.));
+        assert!((timings.slice_imbalance().unwrap() - 1.5).abs() < 1e-9);
+        assert_eq!(Timings::default().slice_imbalance(), None);
+    }
+
+    /// The timers record the pipeline with the `timing` feature and nothing without it.
+    #[test]
+    fn recorded() {
+        let path = std::path::Path::new("tests/test_files/measurements-10000-unique-keys.txt");
+        let summarize = crate::version("v3").unwrap().summarize;
+        take();
+        summarize(path, None, 4, crate::ParseMode::Strict).unwrap();
+        let timings = take();
+        if cfg!(feature = "timing") {
+            // Other tests may record timings at the same time, so there can be more.
+            assert!(timings.slices().len() >= 4);
+            assert!(Phase::ALL
+                .into_iter()
+                .all(|phase| timings.phase(phase) > Duration::ZERO));
+        } else {
+            assert_eq!(timings, Timings::default());
+        }
+    }
+}
@@ -1,10 +1,14 @@
-use std::{cmp::Ordering, collections::HashMap, hash::Hasher, path::Path};
+use std::{cmp::Ordering, collections::HashMap, path::Path};
 
 use anyhow::Result;
-use itertools::Itertools;
-use memmap::MmapOptions;
-use rayon::iter::{ParallelBridge, ParallelIterator};
-use rustc_hash::{FxBuildHasher, FxHasher};
+use rustc_hash::FxBuildHasher;
+
+use crate::{
+    common::{self, Table},
+    error::{line_error, validate_line, ParseError},
+    timing::{self, Phase},
+    ParseMode, SkippedLines, StationStats,
+};
 
 type HashBuilder = FxBuildHasher;
 
@@ -12,7 +16,7 @@ type HashBuilder = FxBuildHasher;
 pub struct SummaryError {}
 
 struct Summary<'a> {
-    data: Vec<(&'a str, f32, f32, f32, u32)>,
+    data: Vec<(&'a str, f32, f32, i64, u32)>,
 }
 
 impl<'a> Summary<'a> {
@@ -24,6 +28,12 @@ impl<'a> Summary<'a> {
         self.data.len()
     }
 
+    fn sort(&mut self) {
+        self.data.sort_by_key(|&(key, _, _, _, _)| key);
+    }
+}
+
+impl Table for Summary<'_> {
     fn merge(self, other: Self) -> Self {
         let mut result = vec![];
         let mut a_iter = self.into_iter().peekable();
@@ -66,14 +76,10 @@ impl<'a> Summary<'a> {
         }
         Self { data: result }
     }
-
-    fn sort(&mut self) {
-        self.data.sort_by_key(|&(key, _, _, _, _)| key);
-    }
 }
 
 impl<'a> IntoIterator for Summary<'a> {
-    type Item = (&'a str, f32, f32, f32, u32);
+    type Item = (&'a str, f32, f32, i64, u32);
     type IntoIter = std::vec::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
@@ -81,66 +87,46 @@ impl<'a> IntoIterator for Summary<'a> {
     }
 }
 
-fn to_string(mut data: Summary) -> String {
-    data.sort();
-    let mut entries = data.into_iter();
-    let mut result = "{".to_string();
-    if let Some((name, min, max, total, count)) = entries.next() {
-        result.push_str(&format!(
-            "{name}={min:.1}/{:.1}/{max:.1}",
-            ((total / (count as f32)) * 10.).round() / 10.
-        ));
-    }
-    for (name, min, max, total, count) in entries {
-        result.push_str(", ");
-        result.push_str(&format!(
-            "{name}={min:.1}/{:.1}/{max:.1}",
-            ((total / (count as f32)) * 10.).round() / 10.
-        ));
-    }
-    result.push_str("}\n");
-    result
+fn to_summary(data: Summary) -> crate::Summary {
+    crate::Summary::from_stations(data.into_iter().map(|(name, min, max, total, count)| {
+        StationStats::new(
+            name,
+            (min * 10.).round() as i32,
+            (max * 10.).round() as i32,
+            total,
+            count as u64,
+        )
+    }))
 }
 
-fn hash_str(s: &[u8]) -> u64 {
-    let mut hash = FxHasher::default();
-
-    hash.write(s);
-    hash.finish()
-}
-
-fn find_split_index(slice: &[u8], index: usize) -> usize {
-    assert!(index <= slice.len());
-    if index == 0 {
-        return index;
-    }
-    let mut split_index = index;
-    while index != slice.len() && slice[split_index] != b'\n' {
-        split_index += 1;
-    }
-    split_index + 1
-}
-
-fn summarize_slice(slice: &[u8]) -> Summary {
-    assert_ne!(slice.last(), Some(&b';'));
+fn summarize_slice<const LENIENT: bool>(
+    slice: &[u8],
+) -> Result<(Summary<'_>, SkippedLines), ParseError> {
     let mut cur_data: Summary = Summary::new();
+    let mut skipped_lines = SkippedLines::new();
 
-    let mut indices: HashMap<u64, usize, HashBuilder> =
+    let mut indices: HashMap<&[u8], usize, HashBuilder> =
         HashMap::with_hasher(HashBuilder::default());
 
     for line in slice.split(|&c| c == b'\n').filter(|line| !line.is_empty()) {
+        if let Err((_, reason)) = validate_line(line) {
+            if LENIENT {
+                skipped_lines.record(reason);
+                continue;
+            }
+            let line_start = line.as_ptr() as usize - slice.as_ptr() as usize;
+            return Err(line_error(slice, line_start));
+        }
         let mut split = line.split(|&c| c == b';');
         let key = split.next().unwrap();
         let value = fast_float::parse(split.next().unwrap()).unwrap();
 
-        let hash = hash_str(key);
-
-        let index = indices.entry(hash).or_insert_with(|| {
+        let index = indices.entry(key).or_insert_with(|| {
             cur_data.data.push((
                 std::str::from_utf8(key).unwrap(),
                 f32::MAX,
                 f32::MIN,
-                0.0,
+                0,
                 0,
             ));
             cur_data.len() - 1
@@ -149,37 +135,38 @@ fn summarize_slice(slice: &[u8]) -> Summary {
         let (_name, min, max, total, count) = &mut cur_data.data[*index];
         *min = min.min(value);
         *max = max.max(value);
-        *total += value;
+        // The sum is kept in tenths, since summing `f32`s makes the mean depend on how the input is split.
+        *total += (value * 10.).round() as i64;
         *count += 1;
     }
 
     cur_data.sort();
-    cur_data
+    Ok((cur_data, skipped_lines))
 }
 
-pub fn summarize(path: &Path, max_bytes: Option<usize>, num_threads: usize) -> Result<String> {
-    // Create buffer for reading file line by line
-    let file = std::fs::File::open(path).unwrap();
-    let file = unsafe { MmapOptions::new().map(&file).unwrap() };
-
-    let len = find_split_index(&file, file.len().min(max_bytes.unwrap_or(usize::MAX)));
-    let total_slice = &file[..len - 1];
+pub fn summarize(
+    path: &Path,
+    max_bytes: Option<usize>,
+    num_threads: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    common::summarize_file(path, |data| {
+        summarize_bytes(data, max_bytes, num_threads, mode)
+    })
+}
 
-    let summary = (0..=num_threads)
-        .map(|i| find_split_index(total_slice, (total_slice.len() * i) / num_threads))
-        .tuple_windows()
-        .map(|(start, end)| {
-            if start == end {
-                &total_slice[start..start]
-            } else {
-                &total_slice[start..(end - 1)]
-            }
-        })
-        .par_bridge()
-        .map(|slice| summarize_slice(slice))
-        .reduce(Summary::new, |a, b| a.merge(b));
-    //.reduce(|a, b| merge_summaries(a, b))
-    //.unwrap();
-
-    Ok(to_string(summary))
+pub fn summarize_bytes(
+    data: &[u8],
+    max_bytes: Option<usize>,
+    num_threads: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    let summarize_slice = match mode {
+        ParseMode::Strict => summarize_slice::<false>,
+        ParseMode::Lenient => summarize_slice::<true>,
+    };
+    let (summary, skipped_lines) =
+        common::summarize_slices(data, max_bytes, num_threads, summarize_slice)?;
+    let summary = timing::time(Phase::IntoResult, || to_summary(summary));
+    Ok(summary.with_skipped_lines(skipped_lines))
 }
@@ -1,233 +1,55 @@
-use std::{cmp::Ordering, collections::HashMap, hash::Hasher, path::Path};
+use std::{collections::HashMap, hash::BuildHasher, path::Path};
 
 use anyhow::Result;
-use itertools::Itertools;
-use memmap::MmapOptions;
 use nohash_hasher::BuildNoHashHasher;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use rustc_hash::FxHasher;
 
-type HashBuilder = BuildNoHashHasher<u64>;
-
-#[derive(Debug, Clone, Copy)]
-struct SummaryEntry<'a> {
-    name: &'a str,
-    min: i32,
-    max: i32,
-    total: i64,
-    count: u32,
-}
-
-impl<'a> SummaryEntry<'a> {
-    /// Creates a new summary entry with the given name.
-    /// HOT
-    fn new(name: &'a str) -> Self {
-        Self {
-            name,
-            min: i32::MAX,
-            max: i32::MIN,
-            total: 0,
-            count: 0,
-        }
-    }
-
-    /// Converts the summary entry into a string to be used in the final output.
-    fn into_string(self) -> String {
-        let Self {
-            name,
-            min,
-            max,
-            total,
-            count,
-        } = self;
-        let min_negative = min < 0;
-        let min = min.abs();
-        let max_negative = max < 0;
-        let max = max.abs();
-        let mean_negative = total < 0;
-        let total = total.abs();
-
-        let min_integer = min / 10;
-        let min_decimal = min % 10;
-        let max_integer = max / 10;
-        let max_decimal = max % 10;
-
-        let min_sign = if min_negative { "-" } else { "" };
-        let max_sign = if max_negative { "-" } else { "" };
-        let mean_sign = if mean_negative { "-" } else { "" };
-
-        let mean_times_ten = (total / count as i64)
-            + if (total.abs() % count as i64) * 2 >= count as i64 {
-                1
-            } else {
-                0
-            };
-        let mean_integer = mean_times_ten / 10;
-        let mean_decimal = mean_times_ten % 10;
-
-        format!(
-            "{name}={min_sign}{min_integer}.{min_decimal}/{mean_sign}{mean_integer}.{mean_decimal}/{max_sign}{max_integer}.{max_decimal}",
-        )
-    }
-
-    /// Update the summary entry with a new value.
-    /// HOT
-    #[inline(always)]
-    fn update(&mut self, value: i32) {
-        self.min = self.min.min(value);
-        self.max = self.max.max(value);
-        self.total += value as i64;
-        self.count += 1;
-    }
-}
-
-struct Summary<'a> {
-    data: Vec<SummaryEntry<'a>>,
-}
-
-impl<'a> Summary<'a> {
-    fn new() -> Self {
-        Self { data: vec![] }
-    }
-
-    /// Creates a new summary from a hashmap.
-    /// The result is sorted by station name.
-    fn from_hashmap(data: HashMap<u64, SummaryEntry<'a>, HashBuilder>) -> Self {
-        Self {
-            data: {
-                let mut vec: Vec<_> = data.into_values().collect();
-                vec.sort_by_key(|entry| entry.name);
-                vec
-            },
-        }
-    }
+use crate::{
+    aggregator::{Aggregated, Aggregator, Stats},
+    common::{self, find_delimiter_long, hash_str, update_station, Summary, SummaryEntry},
+    error::{line_error, skip_line, ParseError},
+    ParseMode, SkippedLines,
+};
 
-    #[cfg(test)]
-    fn len(&self) -> usize {
-        self.data.len()
-    }
-
-    fn merge(self, other: Self) -> Self {
-        let mut result = vec![];
-        let mut a_iter = self.into_iter().peekable();
-        let mut b_iter = other.into_iter().peekable();
-
-        let mut cur_a = a_iter.next();
-        let mut cur_b = b_iter.next();
-        loop {
-            if let Some(a) = cur_a {
-                if let Some(b) = cur_b {
-                    match a.name.cmp(b.name) {
-                        Ordering::Less => {
-                            result.push(a);
-                            cur_a = a_iter.next();
-                        }
-                        Ordering::Equal => {
-                            result.push(SummaryEntry {
-                                min: a.min.min(b.min),
-                                max: a.max.max(b.max),
-                                total: a.total + b.total,
-                                count: a.count + b.count,
-                                ..a
-                            });
-                            cur_a = a_iter.next();
-                            cur_b = b_iter.next();
-                        }
-                        Ordering::Greater => {
-                            result.push(b);
-                            cur_b = b_iter.next();
-                        }
-                    }
-                } else {
-                    result.extend(cur_a.into_iter().chain(a_iter));
-                    break;
-                }
-            } else {
-                result.extend(cur_b.into_iter().chain(b_iter));
-                break;
-            }
-        }
-        Self { data: result }
-    }
-
-    fn sort(&mut self) {
-        self.data.sort_by_key(|entry| entry.name);
-    }
-
-    /// Convert the summary into a string to be used as the final output.
-    fn into_result(mut self) -> String {
-        self.sort();
-        let mut entries = self.into_iter();
-        let mut result = "{".to_string();
-        if let Some(entry) = entries.next() {
-            result.push_str(&entry.into_string());
-        }
-        for entry in entries {
-            result.push_str(", ");
-            result.push_str(&entry.into_string());
-        }
-        result.push_str("}\n");
-        result
-    }
-}
-
-impl<'a> IntoIterator for Summary<'a> {
-    type Item = SummaryEntry<'a>;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
-
-    fn into_iter(self) -> Self::IntoIter {
-        self.data.into_iter()
-    }
-}
-
-/// Given a 16-byte word, find the position of the first semicolon byte.
-/// HOT
-fn find_delimiter_long<const DELIM: u8>(word: u128) -> u8 {
-    const SPREADER: u128 = 0x0101_0101_0101_0101_0101_0101_0101_0101;
-    let delim_pattern: u128 = DELIM as u128 * SPREADER;
-    let input = word ^ delim_pattern;
-    let processed_input = input.wrapping_sub(SPREADER) & !input & (0x80 * SPREADER);
-    processed_input.trailing_zeros() as u8 >> 3 // The position of the first ; byte, or 16 if there is none.
-}
-
-/// Hash a string slice into a u64 using the FxHash algorithm.
-/// HOT
-fn hash_str(s: &[u8]) -> u64 {
-    let mut hash = FxHasher::default();
-
-    hash.write(s);
-    hash.finish()
-}
-
-/// Given a arbitrary index into the slice, find the first following index that is a works as a split index.
-/// This means finding the first newline character after the given index, or the end of the slice if there is none.
-fn find_split_index(slice: &[u8], index: usize) -> usize {
-    assert!(index <= slice.len());
-    if index == 0 {
-        return index;
-    }
-    let mut split_index = index;
-    while index != slice.len() && slice[split_index] != b'\n' {
-        split_index += 1;
-    }
-    split_index + 1
-}
+type HashBuilder = BuildNoHashHasher<u64>;
 
 /// Create a summary of the given slice of bytes.
 /// This is the main function we are interested in optimizing.
+/// Every station starts out as a clone of `prototype`, which is updated with each of its values.
+/// `v2` shares this parser with another hasher `S`, and `vno_assert` with `ASSERTS` false,
+/// which only checks the assertions in debug builds.
 /// HOT
-fn summarize_slice(slice: &[u8]) -> Summary {
+pub(crate) fn summarize_slice<
+    'a,
+    A: Aggregator,
+    S: BuildHasher + Default,
+    const LENIENT: bool,
+    const ASSERTS: bool,
+>(
+    slice: &'a [u8],
+    prototype: &A,
+) -> Result<(Summary<'a, A>, SkippedLines), ParseError> {
     if slice.is_empty() {
-        return Summary::new();
+        return Ok((Summary::new(), SkippedLines::new()));
     }
 
-    assert_ne!(slice.last(), Some(&b';'));
-
-    let mut cur_data: HashMap<u64, SummaryEntry, HashBuilder> =
-        HashMap::with_hasher(HashBuilder::default());
+    let mut cur_data: HashMap<u64, SummaryEntry<A>, S> = HashMap::with_hasher(S::default());
 
     let mut index = 0;
-    assert_ne!(slice.last(), Some(&b'.'));
+    let mut skipped_lines = SkippedLines::new();
+
+    // Handle an invalid line starting at the given index by either skipping it or returning an error.
+    macro_rules! invalid_line {
+        ($line_start:expr) => {
+            if LENIENT {
+                let (reason, next_line_start) = skip_line(slice, $line_start);
+                skipped_lines.record(reason);
+                index = next_line_start;
+                continue;
+            } else {
+                return Err(line_error(slice, $line_start));
+            }
+        };
+    }
 
     while index < slice.len() {
         // Skip empty lines
@@ -236,13 +58,7 @@ fn summarize_slice(slice: &[u8]) -> Summary {
             continue;
         }
 
-        assert_ne!(
-            slice.get(index),
-            Some(&b';'),
-            "A line should never start with a semicolon."
-        );
-
-        if index != 0 {
+        if index != 0 && (ASSERTS || cfg!(debug_assertions)) {
             assert_eq!(
                 slice[index - 1],
                 b'\n',
@@ -256,7 +72,12 @@ fn summarize_slice(slice: &[u8]) -> Summary {
         }
 
         // Find the start and end of the name of the station.
+        // Any unexpected byte from here on means the line is invalid,
+        // in which case `line_error` goes over it again to find out exactly what is wrong.
         let name_start_index = index;
+        if slice[index] == b';' {
+            invalid_line!(name_start_index);
+        }
 
         while let Some(word_slice) = slice.get(index..index + 16) {
             let word = u128::from_le_bytes(word_slice.try_into().unwrap());
@@ -266,9 +87,12 @@ fn summarize_slice(slice: &[u8]) -> Summary {
                 break;
             }
         }
-        while slice[index] != b';' {
+        while slice.get(index).is_some_and(|&b| b != b';') {
             index += 1;
         }
+        if index == slice.len() {
+            invalid_line!(name_start_index);
+        }
         let name_end_index = index;
         let name = &slice[name_start_index..name_end_index];
         index += 1;
@@ -276,108 +100,108 @@ fn summarize_slice(slice: &[u8]) -> Summary {
         // Parse the value.
         // This part is pretty complex. I think I can improve this without sacrificing performance.
         // We start by checking if the value is negative.
-        let negative = if let Some(&first_value_byte) = slice.get(index) {
-            if first_value_byte == b'-' {
-                index += 1;
-                true
-            } else {
-                false
-            }
-        } else {
-            unreachable!("Input should never end right after a semicolon.");
-        };
-        let mut value = if let Some(&first_digit) = slice.get(index) {
-            assert!(
-                first_digit.is_ascii_digit(),
-                "Value should start with a digit."
-            );
-            (first_digit - b'0') as i32
-        } else {
-            unreachable!("Input should never end right after a semicolon or negative sign.");
+        let negative = slice.get(index) == Some(&b'-');
+        if negative {
+            index += 1;
+        }
+        let mut value = match slice.get(index) {
+            Some(&first_digit) if first_digit.is_ascii_digit() => (first_digit - b'0') as i32,
+            _ => invalid_line!(name_start_index),
         };
         index += 1;
-        assert!(
-            slice.len() >= index + 2,
-            "Value should contain at least one digit and a period with a following digit."
-        );
-        loop {
-            if let Some(&b) = slice.get(index) {
-                if b == b'.' {
-                    index += 1;
-                    break;
-                }
-                assert!(
-                    b.is_ascii_digit(),
-                    "Value should only contain digits and a single period."
-                );
-                value = value * 10 + (b - b'0') as i32;
+        // There is at most one more digit before the period.
+        if let Some(&second_digit) = slice.get(index) {
+            if second_digit.is_ascii_digit() {
+                value = value * 10 + (second_digit - b'0') as i32;
                 index += 1;
-            } else {
-                unreachable!("Input should never end in the middle of a value.");
             }
         }
-        assert!(slice[index - 1] == b'.');
-        let decimal = slice
-            .get(index)
-            .expect("Values should contain exactly one decimal.");
-        assert!(decimal.is_ascii_digit());
-        let value = (value * 10 + (decimal - b'0') as i32) * if negative { -1 } else { 1 };
+        if slice.get(index) != Some(&b'.') {
+            invalid_line!(name_start_index);
+        }
+        index += 1;
+        let value = match slice.get(index) {
+            Some(&decimal) if decimal.is_ascii_digit() => {
+                (value * 10 + (decimal - b'0') as i32) * if negative { -1 } else { 1 }
+            }
+            _ => invalid_line!(name_start_index),
+        };
         // We have now fully parsed the value.
 
-        // Update the data for the station with the parsed value.
-        let hash = hash_str(name);
-
-        let city_data = cur_data
-            .entry(hash)
-            .or_insert_with(|| SummaryEntry::new(std::str::from_utf8(name).unwrap()));
-
-        city_data.update(value);
-
-        // Skip to the next line or break if we are at the end of the slice.
+        // Skip to the next line, checking that the line ends here before the value is added,
+        // so the values of invalid lines are never counted.
         index += 1;
-        if let Some(&new_line) = slice.get(index) {
-            if new_line == b'\n' {
-                index += 1;
-            } else {
-                unreachable!("Values should end with a newline.");
-            }
-        } else {
-            break;
+        match slice.get(index) {
+            Some(b'\n') => index += 1,
+            Some(_) => invalid_line!(name_start_index),
+            None => {}
+        }
+
+        // Update the data for the station with the parsed value.
+        if !update_station(&mut cur_data, hash_str(name), name, value, prototype) {
+            invalid_line!(name_start_index);
         }
     }
 
-    Summary::from_hashmap(cur_data)
+    Ok((Summary::from_hashmap(cur_data), skipped_lines))
 }
 
-pub fn summarize(path: &Path, max_bytes: Option<usize>, num_slices: usize) -> Result<String> {
-    // Create buffer for reading file line by line
-    let file = std::fs::File::open(path).unwrap();
-    let file = unsafe { MmapOptions::new().map(&file).unwrap() };
+/// Summarize the file at the given path by memory mapping it.
+pub fn summarize(
+    path: &Path,
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    common::summarize_file(path, |data| {
+        summarize_bytes(data, max_bytes, num_slices, mode)
+    })
+}
 
-    let len = find_split_index(&file, file.len().min(max_bytes.unwrap_or(usize::MAX)));
-    let total_slice = &file[..len - 1];
+/// Summarize a dataset that is already in memory, computing the usual statistics with `Stats`.
+/// If `max_bytes` is given, only the lines starting before that point are included.
+pub fn summarize_bytes(
+    data: &[u8],
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    summarize_bytes_with::<HashBuilder, true>(data, max_bytes, num_slices, mode)
+}
 
-    // Create a slice of the dataset for each thread.
-    let slices = (0..=num_slices)
-        .map(|i| find_split_index(total_slice, (total_slice.len() * i) / num_slices))
-        .tuple_windows()
-        .map(|(start, end)| {
-            if start == end {
-                &total_slice[start..start]
-            } else {
-                &total_slice[start..(end - 1)]
-            }
-        })
-        .collect::<Vec<_>>();
-    // Summarize each slice.
-    let summaries: Vec<Summary> = slices
-        .into_par_iter()
-        .map(|slice| summarize_slice(slice))
-        .collect();
-    // Combine summaries.
-    let summary = summaries.into_iter().reduce(|a, b| a.merge(b)).unwrap();
+/// `summarize_bytes` with the given hasher and assert policy, as described in `summarize_slice`.
+pub(crate) fn summarize_bytes_with<S: BuildHasher + Default, const ASSERTS: bool>(
+    data: &[u8],
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    let summarize_slice = match mode {
+        ParseMode::Strict => summarize_slice::<Stats, S, false, ASSERTS>,
+        ParseMode::Lenient => summarize_slice::<Stats, S, true, ASSERTS>,
+    };
+    common::summarize_bytes(data, max_bytes, num_slices, |slice| {
+        summarize_slice(slice, &Stats::default())
+    })
+}
 
-    Ok(summary.into_result())
+/// Summarize a dataset that is already in memory, computing the statistics given by the aggregator for each station.
+/// Every station starts out as a clone of `prototype`.
+/// If `max_bytes` is given, only the lines starting before that point are included.
+pub fn aggregate_bytes<A: Aggregator + Send + Sync>(
+    data: &[u8],
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    mode: ParseMode,
+    prototype: &A,
+) -> Result<Aggregated<A::Output>> {
+    let summarize_slice = match mode {
+        ParseMode::Strict => summarize_slice::<A, HashBuilder, false, true>,
+        ParseMode::Lenient => summarize_slice::<A, HashBuilder, true, true>,
+    };
+    common::aggregate_bytes(data, max_bytes, num_slices, |slice| {
+        summarize_slice(slice, prototype)
+    })
 }
 
 #[cfg(test)]
@@ -387,7 +211,7 @@ mod test {
     #[test]
     fn single() {
         let slice = &[75, 117, 110, 109, 105, 110, 103, 59, 49, 57, 46, 56];
-        let summary = summarize_slice(slice);
+        let (summary, _) = summarize_slice::<_, HashBuilder, false, true>(slice, &Stats::default()).unwrap();
         assert_eq!(summary.len(), 1);
     }
 }
@@ -0,0 +1,39 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{
+    aggregator::{Squares, Stats},
+    common, ParseMode,
+};
+
+/// The parser of `v3`, which is generic over the `Aggregator` computing the statistics of each station.
+pub use crate::v3::aggregate_bytes;
+
+/// Summarize the file at the given path by memory mapping it, keeping the sum of squares of each station.
+pub fn summarize(
+    path: &Path,
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    common::summarize_file(path, |data| {
+        summarize_bytes(data, max_bytes, num_slices, mode)
+    })
+}
+
+/// Summarize a dataset that is already in memory, computing the usual statistics with `Stats`
+/// along with the sum of squares of each station with `Squares`.
+/// If `max_bytes` is given, only the lines starting before that point are included.
+pub fn summarize_bytes(
+    data: &[u8],
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    let prototype = (Stats::default(), Squares::default());
+    let result = aggregate_bytes(data, max_bytes, num_slices, mode, &prototype)?;
+    Ok(result.into_summary(|name, (stats, sum_squares)| {
+        stats.into_station_stats(name).with_sum_squares(sum_squares)
+    }))
+}
@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{
+    aggregator::{Buckets, Stats},
+    common,
+    vgeneric::aggregate_bytes,
+    ParseMode,
+};
+
+/// Summarize the file at the given path by memory mapping it.
+pub fn summarize(
+    path: &Path,
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    common::summarize_file(path, |data| {
+        summarize_bytes(data, max_bytes, num_slices, mode)
+    })
+}
+
+/// Summarize a dataset that is already in memory, keeping the histogram of each station.
+/// If `max_bytes` is given, only the lines starting before that point are included.
+pub fn summarize_bytes(
+    data: &[u8],
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    let prototype = (Stats::default(), Buckets::default());
+    let result = aggregate_bytes(data, max_bytes, num_slices, mode, &prototype)?;
+    Ok(result.into_summary(|name, (stats, histogram)| {
+        // The histogram gives the sum of squares without computing it in the parser.
+        stats
+            .into_station_stats(name)
+            .with_sum_squares(histogram.sum_squares_tenths())
+            .with_histogram(histogram)
+    }))
+}
@@ -0,0 +1,121 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Result;
+use rustc_hash::FxBuildHasher;
+
+use crate::{
+    common,
+    error::{line_error_with, validate_line_with, Decimals, ParseError},
+    sketch::QuantileSketch,
+    ParseMode, SkippedLines, StationStats,
+};
+
+type HashBuilder = FxBuildHasher;
+
+struct SummaryEntry {
+    min: i32,
+    max: i32,
+    total: i64,
+    total_squares: u64,
+    count: u64,
+    sketch: QuantileSketch,
+}
+
+impl SummaryEntry {
+    fn new() -> Self {
+        Self {
+            min: i32::MAX,
+            max: i32::MIN,
+            total: 0,
+            total_squares: 0,
+            count: 0,
+            sketch: QuantileSketch::default(),
+        }
+    }
+
+    /// Update the summary entry with a new value.
+    /// The sketch gets the exact value, while the other statistics use it rounded to tenths.
+    fn update(&mut self, value: f64) {
+        let tenths = (value * 10.).round() as i32;
+        self.min = self.min.min(tenths);
+        self.max = self.max.max(tenths);
+        self.total += tenths as i64;
+        self.total_squares += (tenths as i64 * tenths as i64) as u64;
+        self.count += 1;
+        self.sketch.update(value);
+    }
+
+    fn into_stats(self, name: &str) -> StationStats {
+        StationStats::new(
+            name,
+            self.min,
+            self.max,
+            self.total,
+            self.count,
+        )
+        .with_sum_squares(self.total_squares)
+        .with_sketch(self.sketch)
+    }
+}
+
+fn summarize_slice<const LENIENT: bool>(
+    slice: &[u8],
+) -> Result<(crate::Summary, SkippedLines), ParseError> {
+    let mut cur_data: HashMap<&str, SummaryEntry, HashBuilder> =
+        HashMap::with_hasher(HashBuilder::default());
+    let mut skipped_lines = SkippedLines::new();
+
+    for line in slice.split(|&c| c == b'\n').filter(|line| !line.is_empty()) {
+        if let Err((_, reason)) = validate_line_with(line, Decimals::Any) {
+            if LENIENT {
+                skipped_lines.record(reason);
+                continue;
+            }
+            let line_start = line.as_ptr() as usize - slice.as_ptr() as usize;
+            return Err(line_error_with(slice, line_start, Decimals::Any));
+        }
+        let (name, value) = line.split_at(line.iter().position(|&c| c == b';').unwrap());
+        let name = std::str::from_utf8(name).unwrap();
+        let value: f64 = fast_float::parse(&value[1..]).unwrap();
+        cur_data
+            .entry(name)
+            .or_insert_with(SummaryEntry::new)
+            .update(value);
+    }
+
+    let summary = crate::Summary::from_stations(
+        cur_data
+            .into_iter()
+            .map(|(name, entry)| entry.into_stats(name)),
+    );
+    Ok((summary, skipped_lines))
+}
+
+/// Summarize the file at the given path by memory mapping it.
+pub fn summarize(
+    path: &Path,
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    common::summarize_file(path, |data| {
+        summarize_bytes(data, max_bytes, num_slices, mode)
+    })
+}
+
+/// Summarize a dataset that is already in memory.
+/// If `max_bytes` is given, only the lines starting before that point are included.
+pub fn summarize_bytes(
+    data: &[u8],
+    max_bytes: Option<usize>,
+    num_slices: usize,
+    mode: ParseMode,
+) -> Result<crate::Summary> {
+    let summarize_slice = match mode {
+        ParseMode::Strict => summarize_slice::<false>,
+        ParseMode::Lenient => summarize_slice::<true>,
+    };
+    let (summary, skipped_lines) =
+        common::summarize_slices(data, max_bytes, num_slices, summarize_slice)?;
+    Ok(summary.with_skipped_lines(skipped_lines))
+}
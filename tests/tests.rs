@@ -12,10 +12,22 @@ macro_rules! brc_tests {
             let data_file_path = file_path.with_extension("txt");
             let out_file_path = file_path.with_extension("out");
             let expected = std::fs::read_to_string(out_file_path).unwrap();
-            let result = brc::v0::summarize(&data_file_path, None, 4);
-            match result {
-                Ok(summary) => assert_eq!(expected, summary),
-                Err(_) => panic!("Error summarizing file {:?}", data_file_path.file_name().unwrap()),
+            for &brc::Version { name, summarize, .. } in brc::VERSIONS {
+                // Up to 17 slices, so slices of the small files are empty or cut lines in every place.
+                for num_slices in 1..=17 {
+                    let result = summarize(&data_file_path, None, num_slices, brc::ParseMode::Strict);
+                    match result {
+                        Ok(summary) => assert_eq!(
+                            expected,
+                            summary.to_string(),
+                            "Version {name} with {num_slices} slices."
+                        ),
+                        Err(error) => panic!(
+                            "Version {name} with {num_slices} slices gave an error for {:?}: {error}",
+                            data_file_path.file_name().unwrap()
+                        ),
+                    }
+                }
             }
         }
     )*
@@ -36,3 +48,637 @@ brc_tests! {
     short: "measurements-short",
     shortest: "measurements-shortest",
 }
+
+/// The versions that only accept values with exactly one decimal, which the tests of invalid input rely on.
+fn one_decimal_versions() -> impl Iterator<Item = &'static brc::Version> {
+    brc::VERSIONS
+        .iter()
+        .filter(|version| !version.capabilities.any_decimals)
+}
+
+/// The name, minimum, maximum, sum and count of a station in tenths of a degree.
+type Statistics = (String, i32, i32, i64, u64);
+
+/// The statistics every version computes for each station along with the skipped lines,
+/// leaving out the sums of squares, histograms and sketches only some versions keep.
+fn statistics(summary: &brc::Summary) -> (Vec<Statistics>, brc::SkippedLines) {
+    let stations = summary
+        .iter()
+        .map(|station| {
+            (
+                station.name().to_string(),
+                station.min_tenths(),
+                station.max_tenths(),
+                station.sum_tenths(),
+                station.count(),
+            )
+        })
+        .collect();
+    (stations, *summary.skipped_lines())
+}
+
+/// The station names in this file are constructed in pairs that share the same FxHash,
+/// so any version that trusts the hash alone will merge them.
+#[test]
+fn hash_collisions() {
+    let file_path = std::path::Path::new(TEST_DIR).join("measurements-hash-collisions");
+    let data_file_path = file_path.with_extension("txt");
+    let expected = std::fs::read_to_string(file_path.with_extension("out")).unwrap();
+    for &brc::Version {
+        name, summarize, ..
+    } in brc::VERSIONS
+    {
+        for num_slices in [1, 4] {
+            let result =
+                summarize(&data_file_path, None, num_slices, brc::ParseMode::Strict).unwrap();
+            assert_eq!(
+                expected,
+                result.to_string(),
+                "Version {name} with {num_slices} slices merged colliding stations."
+            );
+        }
+    }
+}
+
+/// Every version should agree with the generator on the worst cases of each profile.
+#[test]
+fn generated_profiles() {
+    use clap::ValueEnum;
+    for &profile in brc::generate::Profile::value_variants() {
+        let stations = profile.default_stations().min(2_000);
+        let generator = brc::generate::Generator::new(profile, stations, 3).unwrap();
+        let mut data = vec![];
+        let expected = generator.write(20_000, &mut data).unwrap().to_string();
+        for &brc::Version {
+            name,
+            summarize_bytes,
+            ..
+        } in brc::VERSIONS
+        {
+            for num_slices in [1, 4] {
+                let result =
+                    summarize_bytes(&data, None, num_slices, brc::ParseMode::Strict).unwrap();
+                assert_eq!(
+                    expected,
+                    result.to_string(),
+                    "Version {name} with {num_slices} slices on profile {profile:?}."
+                );
+            }
+        }
+    }
+}
+
+macro_rules! brc_error_tests {
+    ($($name:ident: $file_name:expr => ($line:expr, $offset:expr, $reason:ident),)*) => {
+    $(
+        #[test]
+        fn $name() {
+            let data_file_path = std::path::Path::new(TEST_DIR).join($file_name).with_extension("txt");
+            for &brc::Version { name, summarize, .. } in one_decimal_versions() {
+                for num_slices in [1, 2, 4] {
+                    let error = summarize(&data_file_path, None, num_slices, brc::ParseMode::Strict)
+                        .expect_err("Invalid input should give an error.");
+                    let error = error
+                        .downcast_ref::<brc::ParseError>()
+                        .expect("The error should be a parse error.");
+                    assert_eq!(
+                        (error.line(), error.offset(), error.reason()),
+                        ($line, $offset, brc::ParseErrorReason::$reason),
+                        "Version {name} with {num_slices} slices."
+                    );
+                }
+            }
+        }
+    )*
+    }
+}
+
+brc_error_tests! {
+    missing_semicolon: "invalid-missing-semicolon" => (3, 40, MissingSemicolon),
+    empty_name: "invalid-empty-name" => (3, 26, EmptyName),
+    invalid_utf8: "invalid-utf8" => (3, 29, InvalidUtf8),
+    empty_value: "invalid-empty-value" => (3, 36, EmptyValue),
+    nan: "invalid-nan" => (3, 36, InvalidValue),
+    out_of_range: "invalid-out-of-range" => (3, 36, ValueOutOfRange),
+    two_decimals: "invalid-two-decimals" => (3, 40, TooManyDecimals),
+    missing_decimal: "invalid-missing-decimal" => (4, 54, MissingDecimal),
+}
+
+/// Every kind of invalid line mixed in with valid ones, including an invalid last line.
+#[test]
+fn lenient() {
+    use brc::ParseErrorReason::*;
+    let file_path = std::path::Path::new(TEST_DIR).join("lenient-mixed");
+    let data_file_path = file_path.with_extension("txt");
+    let expected = std::fs::read_to_string(file_path.with_extension("out")).unwrap();
+    let v0 =
+        (brc::version("v0").unwrap().summarize)(&data_file_path, None, 1, brc::ParseMode::Lenient)
+            .unwrap();
+    for &brc::Version {
+        name, summarize, ..
+    } in one_decimal_versions()
+    {
+        for num_slices in [1, 2, 4, 7] {
+            let result =
+                summarize(&data_file_path, None, num_slices, brc::ParseMode::Lenient).unwrap();
+            let message = format!("Version {name} with {num_slices} slices.");
+            assert_eq!(expected, result.to_string(), "{message}");
+            assert_eq!(statistics(&v0), statistics(&result), "{message}");
+            let skipped_lines = result.skipped_lines();
+            assert_eq!(skipped_lines.total(), 10, "{message}");
+            assert_eq!(skipped_lines.get(MissingSemicolon), 2, "{message}");
+            assert_eq!(skipped_lines.get(InvalidValue), 2, "{message}");
+            for reason in [
+                EmptyName,
+                InvalidUtf8,
+                EmptyValue,
+                ValueOutOfRange,
+                MissingDecimal,
+                TooManyDecimals,
+            ] {
+                assert_eq!(skipped_lines.get(reason), 1, "{message} {reason}");
+            }
+        }
+    }
+}
+
+/// Summarizing data already in memory should give the same result as summarizing the file.
+#[test]
+fn bytes() {
+    let file_path = std::path::Path::new(TEST_DIR).join("measurements-complex-utf8.txt");
+    let data = std::fs::read(&file_path).unwrap();
+    for &brc::Version {
+        name,
+        summarize,
+        summarize_bytes,
+        ..
+    } in brc::VERSIONS
+    {
+        for num_slices in [1, 4] {
+            assert_eq!(
+                summarize(&file_path, None, num_slices, brc::ParseMode::Strict).unwrap(),
+                summarize_bytes(&data, None, num_slices, brc::ParseMode::Strict).unwrap(),
+                "Version {name} with {num_slices} slices."
+            );
+        }
+        assert!(summarize_bytes(&[], None, 1, brc::ParseMode::Strict)
+            .unwrap()
+            .is_empty());
+    }
+}
+
+/// Streaming with buffers that cut lines in every possible place should give the same summary,
+/// and errors should be reported at their position in the full input.
+#[test]
+fn stream() {
+    let file_path = std::path::Path::new(TEST_DIR).join("measurements-complex-utf8.txt");
+    let data = std::fs::read(&file_path).unwrap();
+    let invalid =
+        std::fs::read(std::path::Path::new(TEST_DIR).join("invalid-missing-decimal.txt")).unwrap();
+    for &brc::Version {
+        name,
+        summarize,
+        summarize_bytes,
+        ..
+    } in one_decimal_versions()
+    {
+        let expected = summarize(&file_path, None, 4, brc::ParseMode::Strict).unwrap();
+        for buffer_size in [1, 2, 3, 7, 16, 64, 4096] {
+            let message = format!("Version {name} with a buffer of {buffer_size} bytes.");
+            let result = brc::stream::summarize_reader(
+                data.as_slice(),
+                summarize_bytes,
+                4,
+                brc::ParseMode::Strict,
+                buffer_size,
+            )
+            .unwrap();
+            assert_eq!(expected, result, "{message}");
+
+            let error = brc::stream::summarize_reader(
+                invalid.as_slice(),
+                summarize_bytes,
+                2,
+                brc::ParseMode::Strict,
+                buffer_size,
+            )
+            .expect_err("Invalid input should give an error.");
+            let error = error.downcast_ref::<brc::ParseError>().unwrap();
+            assert_eq!((error.line(), error.offset()), (4, 54), "{message}");
+        }
+    }
+}
+
+/// Compress the data in every supported way, splitting it into multiple members or frames
+/// at positions that cut lines in half where the format allows it.
+fn compressed_variants(data: &[u8]) -> Vec<(&'static str, Vec<u8>)> {
+    use std::io::Write;
+    let parts: Vec<&[u8]> = data.chunks(data.len() / 5 + 1).collect();
+    let gzip = |part: &[u8]| {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(part).unwrap();
+        encoder.finish().unwrap()
+    };
+    let bgzf_block = |part: &[u8]| {
+        let mut encoder = flate2::GzBuilder::new()
+            .extra(vec![b'B', b'C', 2, 0, 0, 0])
+            .write(vec![], flate2::Compression::default());
+        encoder.write_all(part).unwrap();
+        let mut block = encoder.finish().unwrap();
+        let block_size = u16::try_from(block.len() - 1).unwrap().to_le_bytes();
+        block[16..18].copy_from_slice(&block_size);
+        block
+    };
+    let mut xz = xz2::write::XzEncoder::new(vec![], 6);
+    xz.write_all(data).unwrap();
+    vec![
+        ("gzip", gzip(data)),
+        (
+            "multi-member gzip",
+            parts.iter().flat_map(|part| gzip(part)).collect(),
+        ),
+        (
+            "bgzf",
+            parts.iter().flat_map(|part| bgzf_block(part)).collect(),
+        ),
+        ("zstd", zstd::encode_all(data, 3).unwrap()),
+        (
+            "multi-frame zstd",
+            parts
+                .iter()
+                .flat_map(|part| zstd::bulk::compress(part, 3).unwrap())
+                .collect(),
+        ),
+        ("xz", xz.finish().unwrap()),
+    ]
+}
+
+#[test]
+fn compressed() {
+    let file_path = std::path::Path::new(TEST_DIR).join("measurements-complex-utf8.txt");
+    let data = std::fs::read(&file_path).unwrap();
+    let temp_path = std::env::temp_dir().join(format!("brc-compressed-{}", std::process::id()));
+    for &brc::Version {
+        name,
+        summarize,
+        summarize_bytes,
+        ..
+    } in brc::VERSIONS
+    {
+        let expected = summarize(&file_path, None, 4, brc::ParseMode::Strict).unwrap();
+        for (format, compressed) in compressed_variants(&data) {
+            let message = format!("Version {name} with {format}.");
+            std::fs::write(&temp_path, &compressed).unwrap();
+            let result =
+                brc::input::summarize_file(&temp_path, summarize_bytes, 4, brc::ParseMode::Strict)
+                    .unwrap();
+            assert_eq!(expected, result, "{message}");
+            let result = brc::input::summarize_stream(
+                compressed.as_slice(),
+                summarize_bytes,
+                4,
+                brc::ParseMode::Strict,
+            )
+            .unwrap();
+            assert_eq!(expected, result, "{message}");
+        }
+    }
+    std::fs::remove_file(temp_path).unwrap();
+}
+
+/// Summarizing several files and merging the results should give the same summary as a single
+/// file with all of their lines.
+#[test]
+fn multiple_files() {
+    let pattern = format!("{TEST_DIR}/measurements-[0-9]*.txt");
+    let paths = brc::input::expand_globs(&[pattern]).unwrap();
+    assert_eq!(paths.len(), 6);
+    let mut all_lines = vec![];
+    for path in &paths {
+        all_lines.extend(std::fs::read(path).unwrap());
+        if all_lines.last() != Some(&b'\n') {
+            all_lines.push(b'\n');
+        }
+    }
+    for &brc::Version {
+        name,
+        summarize_bytes,
+        ..
+    } in brc::VERSIONS
+    {
+        let expected = summarize_bytes(&all_lines, None, 4, brc::ParseMode::Strict).unwrap();
+        for num_slices in [1, 4, 16] {
+            let summaries = brc::input::summarize_files(
+                &paths,
+                summarize_bytes,
+                num_slices,
+                brc::ParseMode::Strict,
+            )
+            .unwrap();
+            assert_eq!(summaries.len(), paths.len());
+            let result = summaries
+                .into_iter()
+                .fold(brc::Summary::new(), brc::Summary::merge);
+            assert_eq!(expected, result, "Version {name} with {num_slices} slices.");
+        }
+    }
+}
+
+/// Storing the summaries of shards and merging them later should give the same result as a single pass.
+#[test]
+fn merge_saved_summaries() {
+    let file_path = std::path::Path::new(TEST_DIR).join("measurements-rounding.txt");
+    let data = std::fs::read(&file_path).unwrap();
+    let summarize_bytes = brc::version("v3").unwrap().summarize_bytes;
+    let expected = summarize_bytes(&data, None, 1, brc::ParseMode::Strict).unwrap();
+    for encoding in [
+        brc::encoding::Encoding::Binary,
+        brc::encoding::Encoding::Json,
+    ] {
+        let mut merged = brc::Summary::new();
+        let mut rest = data.as_slice();
+        while !rest.is_empty() {
+            let shard_len = rest.iter().take(70).rposition(|&c| c == b'\n').unwrap() + 1;
+            let (shard, tail) = rest.split_at(shard_len);
+            rest = tail;
+            let summary = summarize_bytes(shard, None, 2, brc::ParseMode::Strict).unwrap();
+            let stored = summary.encode(encoding);
+            merged = merged.merge(brc::Summary::decode(&stored).unwrap());
+        }
+        assert_eq!(expected, merged, "{encoding:?}");
+        assert_eq!(expected.to_string(), merged.to_string(), "{encoding:?}");
+    }
+}
+
+/// Every version with the `squares` capability should track the exact sum of squares,
+/// so the variance is the same for all of them, while the other versions should not compute it at all.
+#[test]
+fn sum_squares() {
+    let file_path = std::path::Path::new(TEST_DIR).join("measurements-rounding.txt");
+    let mut expected = std::collections::BTreeMap::<String, u64>::new();
+    for line in std::fs::read_to_string(&file_path).unwrap().lines() {
+        let (name, value) = line.split_once(';').unwrap();
+        let tenths: i64 = value.replace('.', "").parse().unwrap();
+        *expected.entry(name.to_string()).or_default() += (tenths * tenths) as u64;
+    }
+    for &brc::Version {
+        name,
+        summarize,
+        capabilities,
+        ..
+    } in brc::VERSIONS
+    {
+        let result = summarize(&file_path, None, 4, brc::ParseMode::Strict).unwrap();
+        let sum_squares = result
+            .iter()
+            .map(|station| (station.name().to_string(), station.sum_squares_tenths()))
+            .collect::<std::collections::BTreeMap<_, _>>();
+        let expected = expected
+            .iter()
+            .map(|(station, &squares)| (station.clone(), capabilities.squares.then_some(squares)))
+            .collect();
+        assert_eq!(sum_squares, expected, "Version {name}.");
+    }
+}
+
+#[test]
+fn histogram() {
+    let file_path = std::path::Path::new(TEST_DIR).join("measurements-rounding.txt");
+    let mut values = std::collections::BTreeMap::<String, Vec<i32>>::new();
+    for line in std::fs::read_to_string(&file_path).unwrap().lines() {
+        let (name, value) = line.split_once(';').unwrap();
+        values
+            .entry(name.to_string())
+            .or_default()
+            .push(value.replace('.', "").parse().unwrap());
+    }
+    let expected = brc::v3::summarize(&file_path, None, 4, brc::ParseMode::Strict).unwrap();
+    for num_slices in [1, 2, 4, 7] {
+        let result =
+            brc::vhist::summarize(&file_path, None, num_slices, brc::ParseMode::Strict).unwrap();
+        assert_eq!(result.to_string(), expected.to_string());
+        for station in &result {
+            let mut station_values = values[station.name()].clone();
+            station_values.sort();
+            let histogram = station.histogram().unwrap();
+            assert_eq!(histogram.count(), station_values.len() as u64);
+            for q in [0., 0.05, 0.5, 0.95, 0.99, 1.] {
+                let rank = ((q * station_values.len() as f64).ceil() as usize).max(1);
+                assert_eq!(
+                    histogram.quantile_tenths(q),
+                    station_values[rank - 1],
+                    "Station {} with {num_slices} slices.",
+                    station.name()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn sketch() {
+    let file_path = std::path::Path::new(TEST_DIR).join("measurements-rounding.txt");
+    let expected = brc::v3::summarize(&file_path, None, 4, brc::ParseMode::Strict).unwrap();
+    let result = brc::vsketch::summarize(&file_path, None, 4, brc::ParseMode::Strict).unwrap();
+    assert_eq!(result.to_string(), expected.to_string());
+
+    // Values with up to three decimals, which only the sketch version accepts.
+    let mut values = vec![];
+    let mut data = String::new();
+    for i in 0..200_000u64 {
+        let value = (i * 7919 % 199_999) as f64 / 1000. - 99.999;
+        values.push(value);
+        data.push_str(&format!("station{};{value}\n", i % 3));
+    }
+    assert!(brc::v3::summarize_bytes(data.as_bytes(), None, 4, brc::ParseMode::Strict).is_err());
+    for num_slices in [1, 2, 4, 7] {
+        let result = brc::vsketch::summarize_bytes(
+            data.as_bytes(),
+            None,
+            num_slices,
+            brc::ParseMode::Strict,
+        )
+        .unwrap();
+        assert_eq!(result.len(), 3);
+        for (index, station) in result.iter().enumerate() {
+            let mut station_values: Vec<f64> =
+                values.iter().copied().skip(index).step_by(3).collect();
+            station_values.sort_by(f64::total_cmp);
+            let n = station_values.len();
+            assert_eq!(station.count(), n as u64);
+            assert_eq!(station.quantile(0.), Some(station_values[0]));
+            assert_eq!(station.quantile(1.), Some(station_values[n - 1]));
+            for q in [0.05, 0.5, 0.95, 0.99] {
+                let value = station.quantile(q).unwrap();
+                let rank = station_values.partition_point(|&v| v <= value) as f64;
+                assert!(
+                    (rank / n as f64 - q).abs() < 0.015,
+                    "Quantile {q} of {} with {num_slices} slices.",
+                    station.name()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn aggregators() {
+    use brc::aggregator::{CountAbove, FirstLast, Mode};
+
+    let file_path = std::path::Path::new(TEST_DIR).join("measurements-rounding.txt");
+    let data = std::fs::read(&file_path).unwrap();
+    let mut values = std::collections::BTreeMap::<String, Vec<i32>>::new();
+    for line in std::str::from_utf8(&data).unwrap().lines() {
+        let (name, value) = line.split_once(';').unwrap();
+        values
+            .entry(name.to_string())
+            .or_default()
+            .push(value.replace('.', "").parse().unwrap());
+    }
+    let expected = brc::v3::summarize_bytes(&data, None, 4, brc::ParseMode::Strict).unwrap();
+    let prototype = (
+        (CountAbove::new(200), FirstLast::default()),
+        Mode::default(),
+    );
+    for num_slices in [1, 2, 4, 7] {
+        let summary =
+            brc::vgeneric::summarize_bytes(&data, None, num_slices, brc::ParseMode::Strict)
+                .unwrap();
+        // Apart from the sums of squares, which `v3` leaves out.
+        assert_eq!(statistics(&summary), statistics(&expected));
+
+        let result = brc::vgeneric::aggregate_bytes(
+            &data,
+            None,
+            num_slices,
+            brc::ParseMode::Strict,
+            &prototype,
+        )
+        .unwrap();
+        assert_eq!(result.stations().len(), values.len());
+        for (name, values) in &values {
+            let mut counts = std::collections::BTreeMap::<i32, usize>::new();
+            for &value in values {
+                *counts.entry(value).or_default() += 1;
+            }
+            let max_count = *counts.values().max().unwrap();
+            let mode = *counts
+                .iter()
+                .find(|&(_, &count)| count == max_count)
+                .unwrap()
+                .0;
+            let above = values.iter().filter(|&&value| value > 200).count() as u64;
+            let first_last = (values[0], *values.last().unwrap());
+            assert_eq!(
+                result.get(name),
+                Some(&((above, first_last), mode)),
+                "Station {name} with {num_slices} slices."
+            );
+        }
+    }
+}
+
+/// Every module named like a version should be in the registry, so none can be forgotten.
+#[test]
+fn registry() {
+    let mut modules = std::fs::read_dir("src")
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter_map(|file_name| Some(file_name.strip_suffix(".rs")?.to_string()))
+        .filter(|name| name.starts_with('v'))
+        .collect::<Vec<_>>();
+    modules.sort();
+    let mut names = brc::VERSIONS
+        .iter()
+        .map(|version| version.name.to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(modules, names);
+
+    assert!(brc::version(brc::DEFAULT_VERSION).is_some());
+    assert_eq!(brc::version("3").unwrap().name, "v3");
+    assert!(brc::version("v9").is_none());
+    assert!(brc::version("vsketch").unwrap().capabilities.sketch);
+    // The sum of squares slows down the parser, so the default version does not compute it.
+    assert!(
+        !brc::version(brc::DEFAULT_VERSION)
+            .unwrap()
+            .capabilities
+            .squares
+    );
+    assert!(brc::version("vgeneric").unwrap().capabilities.squares);
+}
+
+/// Summarizing chunks of the input and merging the summaries should give the same result as a single pass,
+/// no matter where the chunks are split.
+fn merge_chunks(
+    measurements: &[(usize, i32)],
+    split_points: &[proptest::sample::Index],
+    num_slices: usize,
+) {
+    const NAMES: [&str; 8] = [
+        "Hamburg",
+        "Ham",
+        "Bulawayo",
+        "Ségou",
+        "Palembang Raya Barat Laut",
+        "St. John's",
+        "a",
+        "Xi'an",
+    ];
+    let data: Vec<u8> = measurements
+        .iter()
+        .flat_map(|&(name, value)| {
+            let sign = if value < 0 { "-" } else { "" };
+            let (whole, decimal) = (value.abs() / 10, value.abs() % 10);
+            format!("{};{sign}{whole}.{decimal}\n", NAMES[name]).into_bytes()
+        })
+        .collect();
+    // Move each split point to the start of the next line.
+    let mut split_points: Vec<usize> = split_points
+        .iter()
+        .map(|index| {
+            let index = index.index(data.len() + 1);
+            data[index..]
+                .iter()
+                .position(|&c| c == b'\n')
+                .map_or(data.len(), |position| index + position + 1)
+        })
+        .collect();
+    split_points.extend([0, data.len()]);
+    split_points.sort();
+    split_points.dedup();
+    for &brc::Version {
+        name,
+        summarize_bytes,
+        capabilities,
+        ..
+    } in brc::VERSIONS
+    {
+        let summarize = |data| summarize_bytes(data, None, num_slices, brc::ParseMode::Strict);
+        let expected = summarize(&data).unwrap();
+        let merged = split_points
+            .windows(2)
+            .map(|chunk| summarize(&data[chunk[0]..chunk[1]]).unwrap())
+            .fold(brc::Summary::new(), brc::Summary::merge);
+        if capabilities.sketch {
+            // Sketches depend on the order they are merged in, so only the statistics are compared.
+            assert_eq!(expected.to_string(), merged.to_string(), "Version {name}.");
+        } else {
+            assert_eq!(expected, merged, "Version {name}.");
+        }
+    }
+}
+
+proptest::proptest! {
+    #![proptest_config(proptest::prelude::ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn merge_chunks_properties(
+        measurements in proptest::collection::vec((0..8usize, -999..=999i32), 0..100),
+        split_points in proptest::collection::vec(proptest::prelude::any::<proptest::sample::Index>(), 0..4),
+        num_slices in 1..=8usize,
+    ) {
+        merge_chunks(&measurements, &split_points, num_slices);
+    }
+}